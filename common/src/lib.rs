@@ -0,0 +1,39 @@
+//! Input helpers shared by several days.
+
+/// Splits `input` into records separated by blank lines.
+///
+/// Each record is the list of its lines, trimmed. Lines made only of
+/// whitespace count as blank, several blank lines in a row separate a single
+/// pair of records, and leading or trailing blank lines produce no empty
+/// record. CRLF line endings are handled like LF ones.
+pub fn records(input: &str) -> Vec<Vec<&str>> {
+    let mut records: Vec<Vec<&str>> = Vec::new();
+    let mut record: Vec<&str> = Vec::new();
+    for l in input.lines().map(str::trim) {
+        if !l.is_empty() {
+            record.push(l);
+        } else if !record.is_empty() {
+            records.push(record);
+            record = Vec::new();
+        }
+    }
+    if !record.is_empty() {
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records() {
+        let expected = vec![vec!["a", "b c"], vec!["d"]];
+        assert_eq!(expected, records("a\nb c\n\nd"));
+        assert_eq!(expected, records("a\r\nb c\r\n\r\nd\r\n"));
+        assert_eq!(expected, records("\n\na\nb c \n \n\t\n\nd\n\n\n"));
+        assert!(records("").is_empty());
+        assert!(records("\r\n  \n").is_empty());
+    }
+}
@@ -11,29 +11,21 @@ type Output1 = usize;
 type Output2 = usize;
 
 fn parse_input(input: &str) -> Input {
-    let mut passports: Vec<RawPassport> = Vec::new();
-    let mut p: RawPassport = RawPassport::new();
-    for l in input.lines() {
-        if l.is_empty() {
-            if p.is_empty() {
-                panic!("Passport must not be empty");
-            }
-            passports.push(p);
-            p = RawPassport::new();
-        } else {
-            l.split(' ').for_each(|kv| {
-                let parts: Vec<&str> = kv.split(':').collect();
-                p.insert(
-                    parts.get(0).unwrap().to_string(),
-                    parts.get(1).unwrap().to_string(),
-                );
-            });
-        }
-    }
-    if !p.is_empty() {
-        passports.push(p);
-    }
-    passports
+    common::records(input)
+        .iter()
+        .map(|lines| {
+            lines
+                .iter()
+                .flat_map(|l| l.split_whitespace())
+                .map(|kv| {
+                    let mut parts = kv.splitn(2, ':');
+                    let key = parts.next().unwrap().to_string();
+                    let value = parts.next().unwrap().to_string();
+                    (key, value)
+                })
+                .collect::<RawPassport>()
+        })
+        .collect()
 }
 
 fn solve_part_1(input: &Input) -> Output1 {
@@ -218,3 +210,19 @@ fn main() {
     let part_2_result = solve_part_2(&input);
     println!("Part 2: {:?}", part_2_result);
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn test_parse_input() {
+        let input = "ecl:gry pid:860033327\r\nbyr:1937  hgt:183cm\r\n\r\niyr:2013\r\n \t\r\n\r\nhcl:#cfa07d eyr:\r\n";
+        let passports = parse_input(input);
+        assert_eq!(3, passports.len());
+        assert_eq!(4, passports[0].len());
+        assert_eq!("183cm", passports[0]["hgt"]);
+        assert_eq!("2013", passports[1]["iyr"]);
+        assert_eq!("#cfa07d", passports[2]["hcl"]);
+        assert_eq!("", passports[2]["eyr"]);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{env, error::Error, fs, str::FromStr};
+use std::{collections::HashSet, env, error::Error, fs, str::FromStr};
 
 fn get_input_path() -> String {
     let args: Vec<String> = env::args().collect();
@@ -6,12 +6,104 @@ fn get_input_path() -> String {
 }
 
 struct Seat {
-    row: u8,
-    col: u8,
+    row: u16,
+    col: u16,
 }
-type Input = Vec<Seat>;
-type Output1 = u16;
-type Output2 = u16;
+
+/// Describes how the boarding passes of an aircraft encode seats: how many
+/// partitions select the row and the column, and which letters pick the
+/// lower and upper halves.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    pub row_bits: u8,
+    pub col_bits: u8,
+    pub row_letters: (char, char),
+    pub col_letters: (char, char),
+}
+
+impl Layout {
+    /// 128 rows of 8 seats, `FBFBBFFRLR` style.
+    const CLASSIC: Layout = Layout {
+        row_bits: 7,
+        col_bits: 3,
+        row_letters: ('F', 'B'),
+        col_letters: ('L', 'R'),
+    };
+    const MAX_BITS: u8 = 16;
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.row_bits > Self::MAX_BITS || self.col_bits > Self::MAX_BITS {
+            return Err(Box::<dyn Error>::from(
+                "Layout can't use more than 16 partitions for rows or columns",
+            ));
+        }
+        let letters = [
+            self.row_letters.0,
+            self.row_letters.1,
+            self.col_letters.0,
+            self.col_letters.1,
+        ];
+        if letters
+            .iter()
+            .enumerate()
+            .any(|(idx, l)| letters[idx + 1..].contains(l))
+        {
+            return Err(Box::<dyn Error>::from("Layout letters must all differ"));
+        }
+        Ok(())
+    }
+
+    /// Seat of a boarding pass, the layout must be valid.
+    pub fn decode(&self, s: &str) -> Result<Seat, Box<dyn Error>> {
+        let len = (self.row_bits + self.col_bits) as usize;
+        if s.chars().count() != len {
+            return Err(format!("Boarding pass must be {} chars long", len).into());
+        }
+        let split = s
+            .char_indices()
+            .nth(self.row_bits as usize)
+            .map_or(s.len(), |(pos, _)| pos);
+        let (fb, lr) = s.split_at(split);
+        let fb = parse_bin_partitions(fb, self.row_letters.0, self.row_letters.1)?;
+        let lr = parse_bin_partitions(lr, self.col_letters.0, self.col_letters.1)?;
+        let row = bin_space_value(&fb)?;
+        let col = bin_space_value(&lr)?;
+        Ok(Seat { row, col })
+    }
+
+    pub fn encode(&self, seat: &Seat) -> String {
+        let mut pass = bin_space_chars(seat.row, self.row_bits, self.row_letters);
+        pass.push_str(&bin_space_chars(seat.col, self.col_bits, self.col_letters));
+        pass
+    }
+
+    pub fn seat_id(&self, seat: &Seat) -> u32 {
+        (seat.row as u32) << self.col_bits | seat.col as u32
+    }
+
+    pub fn seat(&self, id: u32) -> Seat {
+        Seat {
+            row: (id >> self.col_bits) as u16,
+            col: (id & ((1 << self.col_bits) - 1)) as u16,
+        }
+    }
+
+    pub fn rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> u32 {
+        1 << self.col_bits
+    }
+}
+
+struct Manifest {
+    pub layout: Layout,
+    pub seats: Vec<Seat>,
+}
+type Input = Manifest;
+type Output1 = u32;
+type Output2 = u32;
 
 #[derive(Debug)]
 enum BinPart {
@@ -19,54 +111,58 @@ enum BinPart {
     High,
 }
 
-fn parse_bin_partitions(s: &str, low: char, high: char) -> Vec<BinPart> {
+fn parse_bin_partitions(s: &str, low: char, high: char) -> Result<Vec<BinPart>, Box<dyn Error>> {
     s.chars()
         .map(|c| {
             if c == low {
-                BinPart::Low
+                Ok(BinPart::Low)
             } else if c == high {
-                BinPart::High
+                Ok(BinPart::High)
             } else {
-                panic!("Wrong bin split char")
+                Err(format!("Wrong bin split char '{}'", c).into())
             }
         })
         .collect()
 }
 
-fn bin_space_value(splits: &[BinPart]) -> u8 {
-    let mut lower = 0;
-    let mut upper = 2u8.pow(splits.len() as u32) - 1;
-    splits.iter().for_each(|val| {
-        let diff = upper - lower;
-        let mid = lower + diff / 2;
-        match val {
-            BinPart::Low => upper = mid,
-            BinPart::High => lower = mid + diff % 2,
-        }
-    });
-    if lower != upper {
-        panic!("Partitioning didn't converge");
+fn bin_space_value(splits: &[BinPart]) -> Result<u16, Box<dyn Error>> {
+    if splits.len() > Layout::MAX_BITS as usize {
+        return Err(Box::<dyn Error>::from(
+            "Too many partitions to fit in 16 bits",
+        ));
     }
-    lower
+    // keeping the lower or upper half is one bit of the value, MSB first
+    Ok(splits.iter().fold(0, |value, part| match part {
+        BinPart::Low => value << 1,
+        BinPart::High => value << 1 | 1,
+    }))
+}
+
+fn bin_space_chars(value: u16, bits: u8, (low, high): (char, char)) -> String {
+    (0..bits)
+        .rev()
+        .map(|bit| if value >> bit & 1 == 0 { low } else { high })
+        .collect()
 }
 
 impl FromStr for Seat {
     type Err = Box<dyn Error>;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (fb, lr) = s.split_at(7);
-        let fb = parse_bin_partitions(fb, 'F', 'B');
-        let lr = parse_bin_partitions(lr, 'L', 'R');
-        let row = bin_space_value(&fb);
-        let col = bin_space_value(&lr);
-        Ok(Seat { row, col })
+        Layout::CLASSIC.decode(s)
     }
 }
 
-fn parse_input(input: &str) -> Input {
-    input.lines().map(|l| l.parse::<Seat>().unwrap()).collect()
+fn parse_input(input: &str, layout: Layout) -> Result<Input, Box<dyn Error>> {
+    layout.validate()?;
+    let seats = input
+        .lines()
+        .map(|l| layout.decode(l))
+        .collect::<Result<Vec<Seat>, _>>()?;
+    Ok(Manifest { layout, seats })
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod test {
     use super::*;
     #[test]
@@ -76,45 +172,208 @@ mod test {
         assert_eq!(44, seat.row);
         assert_eq!(5, seat.col);
     }
-}
 
-fn seat_id(seat: &Seat) -> u16 {
-    seat.row as u16 * 8 + seat.col as u16
+    #[test]
+    fn test_seat_id() {
+        let seat: Seat = "FBFBBFFRLR".parse().unwrap();
+        assert_eq!(357, Layout::CLASSIC.seat_id(&seat));
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let layout = Layout {
+            row_bits: 16,
+            col_bits: 2,
+            row_letters: ('A', 'Z'),
+            col_letters: ('<', '>'),
+        };
+        let seat = layout.decode("ZZZZZZZZZZZZZZZA><").unwrap();
+        assert_eq!(u16::MAX - 1, seat.row);
+        assert_eq!(2, seat.col);
+        assert_eq!((65534 << 2) + 2, layout.seat_id(&seat));
+    }
+
+    #[test]
+    fn test_encode() {
+        let seat = Seat { row: 44, col: 5 };
+        assert_eq!("FBFBBFFRLR", Layout::CLASSIC.encode(&seat));
+        let seat = Layout::CLASSIC.seat(820);
+        assert_eq!("BBFFBBFRLL", Layout::CLASSIC.encode(&seat));
+        let decoded: Seat = Layout::CLASSIC.encode(&seat).parse().unwrap();
+        assert_eq!(820, Layout::CLASSIC.seat_id(&decoded));
+    }
+
+    #[test]
+    fn test_render_seat_map() {
+        let layout = Layout {
+            row_bits: 1,
+            col_bits: 2,
+            ..Layout::CLASSIC
+        };
+        let input = parse_input("FLL\nFLR\nBRR\n", layout).unwrap();
+        assert_eq!("##..\n.O.#\n", render_seat_map(&input, Some(5)).unwrap());
+        let layout = Layout {
+            row_bits: 16,
+            col_bits: 16,
+            ..Layout::CLASSIC
+        };
+        let input = parse_input("", layout).unwrap();
+        assert!(render_seat_map(&input, None).is_err());
+    }
+
+    #[test]
+    fn test_free_seats() {
+        let input = parse_input("FFFFFFFLLR\nFFFFFFFLRR\nFFFFFFFRRL\n", Layout::CLASSIC).unwrap();
+        assert_eq!(vec![2, 4, 5], free_seats(&input).unwrap());
+        assert!(solve_part_2(&input).is_err());
+        let input = parse_input("FFFFFFFLLR\nFFFFFFFLRR\n", Layout::CLASSIC).unwrap();
+        assert_eq!(2, solve_part_2(&input).unwrap());
+        let input = parse_input("FFFFFFFLLR\nFFFFFFFLRL\n", Layout::CLASSIC).unwrap();
+        assert!(solve_part_2(&input).is_err());
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        let input = parse_input("FFFFFFFLLR\nBBBBBBBRRR\nFFFFFFFLLR\n", Layout::CLASSIC).unwrap();
+        assert!(free_seats(&input).is_err());
+        let input = Manifest {
+            layout: Layout::CLASSIC,
+            seats: vec![Seat { row: 3, col: 1 }, Seat { row: 3, col: 8 }],
+        };
+        assert!(free_seats(&input).is_err());
+    }
+
+    #[test]
+    fn test_invalid_pass() {
+        assert!("FBFBBFFRL".parse::<Seat>().is_err());
+        assert!("FBFBXFFRLR".parse::<Seat>().is_err());
+        assert!("FBFBBFFRLB".parse::<Seat>().is_err());
+        let layout = Layout {
+            row_bits: 17,
+            ..Layout::CLASSIC
+        };
+        assert!(parse_input(&"F".repeat(20), layout).is_err());
+        // checked even without any boarding pass
+        let layout = Layout {
+            row_bits: 32,
+            ..Layout::CLASSIC
+        };
+        assert!(parse_input("", layout).is_err());
+    }
 }
 
 fn solve_part_1(input: &Input) -> Output1 {
-    input.iter().map(|s| seat_id(s)).max().unwrap()
+    input
+        .seats
+        .iter()
+        .map(|s| input.layout.seat_id(s))
+        .max()
+        .unwrap()
+}
+
+/// Largest seat map rendered, in characters.
+const MAX_MAP_SIZE: usize = 1 << 24;
+
+/// Draws one line per row of the plane: `#` for taken seats, `.` for free
+/// ones and `O` for ours.
+fn render_seat_map(input: &Input, ours: Option<u32>) -> Result<String, Box<dyn Error>> {
+    let layout = &input.layout;
+    let size = (layout.rows() as usize)
+        .checked_mul(layout.cols() as usize + 1)
+        .filter(|size| *size <= MAX_MAP_SIZE)
+        .ok_or("Seat map too large to render")?;
+    let taken: HashSet<u32> = input.seats.iter().map(|s| layout.seat_id(s)).collect();
+    let mut map = String::with_capacity(size);
+    for row in 0..layout.rows() {
+        for col in 0..layout.cols() {
+            let id = row << layout.col_bits | col;
+            map.push(if Some(id) == ours {
+                'O'
+            } else if taken.contains(&id) {
+                '#'
+            } else {
+                '.'
+            });
+        }
+        map.push('\n');
+    }
+    Ok(map)
+}
+
+/// Checks the manifest can be trusted: every seat is inside the plane and no
+/// boarding pass was issued twice. Returns the sorted seat IDs.
+fn checked_seat_ids(input: &Input) -> Result<Vec<u32>, Box<dyn Error>> {
+    let layout = &input.layout;
+    let mut seat_ids: Vec<u32> = Vec::with_capacity(input.seats.len());
+    for seat in input.seats.iter() {
+        if seat.row as u32 >= layout.rows() || seat.col as u32 >= layout.cols() {
+            return Err(format!(
+                "Seat row {} col {} is outside the plane",
+                seat.row, seat.col
+            )
+            .into());
+        }
+        seat_ids.push(layout.seat_id(seat));
+    }
+    seat_ids.sort_unstable();
+    if let Some(dup) = seat_ids.windows(2).find(|pair| pair[0] == pair[1]) {
+        let pass = layout.encode(&layout.seat(dup[0]));
+        return Err(format!("Boarding pass {} (seat {}) is duplicated", pass, dup[0]).into());
+    }
+    Ok(seat_ids)
 }
 
-fn solve_part_2_old(input: &Input) -> Output2 {
-    let mut seat_ids: Vec<u16> = input.iter().map(|s| seat_id(s)).collect();
-    seat_ids.sort();
-    let head = seat_ids.iter();
-    let tail = seat_ids.iter().skip(1);
-    head.zip(tail).find(|(&h, &t)| t > h + 1).unwrap().0 + 1
+/// Lists every free seat ID between the first and last taken seats.
+fn free_seats(input: &Input) -> Result<Vec<u32>, Box<dyn Error>> {
+    let seat_ids = checked_seat_ids(input)?;
+    let free = seat_ids
+        .windows(2)
+        .flat_map(|pair| pair[0] + 1..pair[1])
+        .collect();
+    Ok(free)
 }
 
-fn solve_part_2(input: &Input) -> Output2 {
-    // 1 to N sum is N(N+1)/2
-    // sum of all seat IDs up to the max is a sum of:
-    // - all unused front seats
-    // - all used seats
-    // - our seat
-    // simply substract front unused and used from the max sum
-    let min = input.iter().map(|s| seat_id(s)).min().unwrap() as u64;
-    let max = input.iter().map(|s| seat_id(s)).max().unwrap() as u64;
-    let used_seats_id_sum: u64 = input.iter().map(|s| seat_id(s) as u64).sum();
-    let max_id_sum = max * (max + 1) / 2;
-    let min_id_sum = (min - 1) * min / 2;
-    (max_id_sum - used_seats_id_sum - min_id_sum) as u16
+fn solve_part_2(input: &Input) -> Result<Output2, Box<dyn Error>> {
+    let free = free_seats(input)?;
+    match free.as_slice() {
+        [ours] => Ok(*ours),
+        [] => Err(Box::<dyn Error>::from("No free seat left for us")),
+        _ => Err(format!("Expected a single free seat, found {}", free.len()).into()),
+    }
+}
+
+fn get_layout() -> Result<Layout, Box<dyn Error>> {
+    // row and column partition counts may follow the input path
+    let args: Vec<String> = env::args().filter(|a| !a.starts_with("--")).collect();
+    let layout = match (args.get(2), args.get(3)) {
+        (Some(rows), Some(cols)) => Layout {
+            row_bits: rows
+                .parse()
+                .map_err(|_| format!("Invalid row partition count {}", rows))?,
+            col_bits: cols
+                .parse()
+                .map_err(|_| format!("Invalid column partition count {}", cols))?,
+            ..Layout::CLASSIC
+        },
+        _ => Layout::CLASSIC,
+    };
+    layout.validate()?;
+    Ok(layout)
 }
 
 fn main() {
     let input_path = get_input_path();
     let raw_input = fs::read_to_string(input_path).unwrap();
-    let input = parse_input(&raw_input);
+    let input = get_layout()
+        .and_then(|layout| parse_input(&raw_input, layout))
+        .unwrap();
     let part_1_result = solve_part_1(&input);
     println!("Part 1: {:?}", part_1_result);
-    let part_2_result = solve_part_2(&input);
+    let part_2_result = solve_part_2(&input).unwrap();
     println!("Part 2: {:?}", part_2_result);
+    let our_seat = input.layout.seat(part_2_result);
+    println!("Boarding pass: {}", input.layout.encode(&our_seat));
+    if env::args().any(|a| a == "--map") {
+        print!("{}", render_seat_map(&input, Some(part_2_result)).unwrap());
+    }
 }
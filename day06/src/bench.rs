@@ -0,0 +1,77 @@
+//! Times the bitset answers against the original `HashSet<char>` ones.
+//! Run with `--bench` on a large survey file.
+
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+const RUNS: u32 = 10;
+
+type HashAnswers = HashSet<char>;
+
+fn hash_parse(input: &str) -> Vec<Vec<HashAnswers>> {
+    common::records(input)
+        .iter()
+        .map(|group| group.iter().map(|l| l.chars().collect()).collect())
+        .collect()
+}
+
+fn hash_union(input: &[Vec<HashAnswers>]) -> usize {
+    input
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .fold(HashAnswers::new(), |mut set, answers| {
+                    set.extend(answers.iter());
+                    set
+                })
+                .len()
+        })
+        .sum()
+}
+
+fn hash_intersection(input: &[Vec<HashAnswers>]) -> usize {
+    input
+        .iter()
+        .filter_map(|group| {
+            let (first, others) = group.split_first()?;
+            let set = others.iter().fold(first.clone(), |mut set, answers| {
+                set.retain(|a| answers.contains(a));
+                set
+            });
+            Some(set.len())
+        })
+        .sum()
+}
+
+fn time<T>(mut f: impl FnMut() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let mut res = f();
+    for _ in 1..RUNS {
+        res = f();
+    }
+    (res, start.elapsed() / RUNS)
+}
+
+pub fn run(raw_input: &str) {
+    let (hash_input, hash_parse_time) = time(|| hash_parse(raw_input));
+    let (bits_input, bits_parse_time) = time(|| super::parse_input(raw_input));
+    let (hash_any, hash_any_time) = time(|| hash_union(&hash_input));
+    let (bits_any, bits_any_time) = time(|| super::solve_part_1(&bits_input));
+    let (hash_all, hash_all_time) = time(|| hash_intersection(&hash_input));
+    let (bits_all, bits_all_time) = time(|| super::solve_part_2(&bits_input));
+    assert_eq!(hash_any, bits_any);
+    assert_eq!(hash_all, bits_all);
+    println!("average of {} runs   hashset    bitset", RUNS);
+    for (name, hash, bits) in [
+        ("parse", hash_parse_time, bits_parse_time),
+        ("union", hash_any_time, bits_any_time),
+        ("intersection", hash_all_time, bits_all_time),
+    ]
+    .iter()
+    {
+        println!("{:<20} {:>9.3?} {:>9.3?}", name, hash, bits);
+    }
+}
@@ -1,63 +1,209 @@
-use std::{collections::HashSet, env, fs};
+mod bench;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env,
+    error::Error,
+    fs,
+    iter::FromIterator,
+    str::FromStr,
+};
 
 fn get_input_path() -> String {
     let args: Vec<String> = env::args().collect();
     args.get(1).unwrap().clone()
 }
 
-type YesAnswers = HashSet<char>;
+/// Set of questions answered yes. Questions `a` to `z` are bits of a `u32`
+/// so set operations are single instructions, any other question falls back
+/// to a `BTreeSet`.
+#[derive(Debug, Clone)]
+enum QuestionSet {
+    Bits(u32),
+    Chars(BTreeSet<char>),
+}
+
+impl QuestionSet {
+    pub fn new() -> Self {
+        QuestionSet::Bits(0)
+    }
+
+    fn bit(c: char) -> Option<u32> {
+        if c.is_ascii_lowercase() {
+            Some(1 << (c as u8 - b'a'))
+        } else {
+            None
+        }
+    }
+
+    fn to_chars(&self) -> BTreeSet<char> {
+        match self {
+            QuestionSet::Bits(bits) => (b'a'..=b'z')
+                .map(char::from)
+                .filter(|c| bits & Self::bit(*c).unwrap() != 0)
+                .collect(),
+            QuestionSet::Chars(chars) => chars.clone(),
+        }
+    }
+
+    pub fn insert(&mut self, c: char) {
+        match (&mut *self, Self::bit(c)) {
+            (QuestionSet::Bits(bits), Some(bit)) => *bits |= bit,
+            (QuestionSet::Bits(_), None) => {
+                let mut chars = self.to_chars();
+                chars.insert(c);
+                *self = QuestionSet::Chars(chars);
+            }
+            (QuestionSet::Chars(chars), _) => {
+                chars.insert(c);
+            }
+        }
+    }
+
+    fn combine(
+        &self,
+        other: &Self,
+        on_bits: fn(u32, u32) -> u32,
+        on_chars: fn(&BTreeSet<char>, &BTreeSet<char>) -> BTreeSet<char>,
+    ) -> Self {
+        match (self, other) {
+            (QuestionSet::Bits(a), QuestionSet::Bits(b)) => QuestionSet::Bits(on_bits(*a, *b)),
+            _ => QuestionSet::Chars(on_chars(&self.to_chars(), &other.to_chars())),
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a | b, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a & b, |a, b| a & b)
+    }
+
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        self.combine(other, |a, b| a ^ b, |a, b| a ^ b)
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            QuestionSet::Bits(bits) => bits.count_ones() as usize,
+            QuestionSet::Chars(chars) => chars.len(),
+        }
+    }
+
+    /// Answered questions in alphabetical order.
+    pub fn chars(&self) -> Vec<char> {
+        self.to_chars().into_iter().collect()
+    }
+}
+
+impl FromIterator<char> for QuestionSet {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        iter.into_iter().fold(QuestionSet::new(), |mut set, c| {
+            set.insert(c);
+            set
+        })
+    }
+}
+
+type YesAnswers = QuestionSet;
 type GroupAnswers = Vec<YesAnswers>;
 type Input = Vec<GroupAnswers>;
 type Output1 = usize;
 type Output2 = usize;
 
 fn parse_input(input: &str) -> Input {
-    input
-        .split("\n\n") // split on empty lines, 2 successive new lines
+    common::records(input)
+        .iter()
         .map(|group| {
             group
-                .lines()
-                .map(|l| {
-                    l.chars().fold(YesAnswers::new(), |mut set, c| {
-                        set.insert(c);
-                        set
-                    })
-                })
+                .iter()
+                .map(|l| l.chars().collect::<YesAnswers>())
                 .collect::<GroupAnswers>()
         })
         .collect::<Input>()
 }
 
-fn solve_part_1(input: &Input) -> Output1 {
-    input
+/// Number of members of a group who answered yes to each question.
+type Histogram = BTreeMap<char, usize>;
+
+fn histogram(group: &GroupAnswers) -> Histogram {
+    group
         .iter()
-        .map(|group| {
-            let set = group.iter().fold(YesAnswers::new(), |mut set, answers| {
-                answers.iter().for_each(|a| {
-                    set.insert(*a);
-                });
-                set
-            });
-            set.len()
+        .flat_map(|answers| answers.chars())
+        .fold(Histogram::new(), |mut hist, a| {
+            *hist.entry(a).or_insert(0) += 1;
+            hist
         })
-        .sum()
 }
 
-fn solve_part_2(input: &Input) -> Output2 {
+/// Questions to ask about the answers of each group.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Query {
+    /// answered by anyone
+    Union,
+    /// answered by everyone
+    Intersection,
+    /// answered by an odd number of members
+    SymmetricDifference,
+    /// answered by at least k members
+    AtLeast(usize),
+}
+
+impl FromStr for Query {
+    type Err = Box<dyn Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(Query::Union),
+            "all" => Ok(Query::Intersection),
+            "xor" => Ok(Query::SymmetricDifference),
+            _ => {
+                let k = s
+                    .strip_prefix("atleast:")
+                    .ok_or("Query must be any, all, xor or atleast:<k>")?;
+                Ok(Query::AtLeast(k.parse()?))
+            }
+        }
+    }
+}
+
+fn group_query(group: &GroupAnswers, query: Query) -> YesAnswers {
+    match query {
+        Query::Union => group
+            .iter()
+            .fold(YesAnswers::new(), |set, answers| set.union(answers)),
+        Query::Intersection => match group.split_first() {
+            Some((first, others)) => others
+                .iter()
+                .fold(first.clone(), |set, answers| set.intersection(answers)),
+            None => YesAnswers::new(),
+        },
+        Query::SymmetricDifference => group.iter().fold(YesAnswers::new(), |set, answers| {
+            set.symmetric_difference(answers)
+        }),
+        Query::AtLeast(k) => histogram(group)
+            .into_iter()
+            .filter(|(_, count)| *count >= k)
+            .map(|(a, _)| a)
+            .collect(),
+    }
+}
+
+fn count_query(input: &Input, query: Query) -> usize {
     input
         .iter()
-        .map(|group| {
-            group
-                .iter()
-                .fold(group.iter().next().unwrap().clone(), |mut set, answers| {
-                    set.retain(|a| answers.contains(a));
-                    set
-                })
-                .len()
-        })
+        .map(|group| group_query(group, query).len())
         .sum()
 }
 
+fn solve_part_1(input: &Input) -> Output1 {
+    count_query(input, Query::Union)
+}
+
+fn solve_part_2(input: &Input) -> Output2 {
+    count_query(input, Query::Intersection)
+}
+
 fn main() {
     let input_path = get_input_path();
     let raw_input = fs::read_to_string(input_path).unwrap();
@@ -66,6 +212,14 @@ fn main() {
     println!("Part 1: {:?}", part_1_result);
     let part_2_result = solve_part_2(&input);
     println!("Part 2: {:?}", part_2_result);
+    // an extra query may follow the input path, e.g. "atleast:3"
+    if let Some(query) = env::args().skip(2).find(|a| !a.starts_with("--")) {
+        let query: Query = query.parse().unwrap();
+        println!("{:?}: {}", query, count_query(&input, query));
+    }
+    if env::args().any(|a| a == "--bench") {
+        bench::run(&raw_input);
+    }
 }
 
 #[cfg(test)]
@@ -98,4 +252,49 @@ b";
         let res = solve_part_2(&input);
         assert_eq!(6, res);
     }
+
+    #[test]
+    fn test_queries() {
+        let input = parse_input("abc\nabd\nae");
+        let group = &input[0];
+        assert_eq!(
+            vec!['a', 'c', 'd', 'e'],
+            group_query(group, Query::SymmetricDifference).chars()
+        );
+        assert_eq!(
+            vec!['a', 'b'],
+            group_query(group, Query::AtLeast(2)).chars()
+        );
+        assert_eq!(vec!['a'], group_query(group, Query::Intersection).chars());
+        assert_eq!(5, group_query(group, Query::Union).len());
+        let hist = histogram(group);
+        assert_eq!(Some(&3), hist.get(&'a'));
+        assert_eq!(Some(&1), hist.get(&'e'));
+        assert_eq!(Query::AtLeast(3), "atleast:3".parse().unwrap());
+        assert!("most".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn test_question_set_fallback() {
+        let bits: QuestionSet = "abz".chars().collect();
+        assert!(matches!(bits, QuestionSet::Bits(_)));
+        let chars: QuestionSet = "aB?".chars().collect();
+        assert!(matches!(chars, QuestionSet::Chars(_)));
+        assert_eq!(vec!['?', 'B', 'a'], chars.chars());
+        assert_eq!(vec!['a'], bits.intersection(&chars).chars());
+        assert_eq!(5, bits.union(&chars).len());
+        assert_eq!(
+            vec!['?', 'B', 'b', 'z'],
+            bits.symmetric_difference(&chars).chars()
+        );
+    }
+
+    #[test]
+    fn test_windows_input() {
+        let crlf = EXAMPLE_INPUT.replace('\n', "\r\n") + "\r\n\r\n \r\n";
+        let input = parse_input(&crlf);
+        assert_eq!(5, input.len());
+        assert_eq!(11, solve_part_1(&input));
+        assert_eq!(6, solve_part_2(&input));
+    }
 }
@@ -0,0 +1,93 @@
+use super::{BagGraph, BagId};
+use std::fmt::Write;
+
+/// Part of the graph to export.
+#[derive(Debug, Clone, Copy)]
+pub enum Scope<'a> {
+    All,
+    /// the bag and everything it contains
+    From(&'a str),
+    /// the bag and everything that can contain it
+    To(&'a str),
+}
+
+impl BagGraph {
+    /// Bags reachable from `start` following `next` edges, `start` included.
+    fn reachable(&self, start: BagId, next: impl Fn(BagId) -> Vec<BagId>) -> Vec<bool> {
+        let mut seen = vec![false; self.names.len()];
+        seen[start] = true;
+        let mut to_visit = vec![start];
+        while let Some(visiting) = to_visit.pop() {
+            for bag in next(visiting) {
+                if !seen[bag] {
+                    seen[bag] = true;
+                    to_visit.push(bag);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Graphviz export of the containment graph, edges go from the holder to
+    /// the held bag and are labelled with the quantity. Returns `None` when
+    /// the scope names an unknown bag.
+    pub fn to_dot(&self, scope: Scope) -> Option<String> {
+        let kept = match scope {
+            Scope::All => vec![true; self.names.len()],
+            Scope::From(bag) => self.reachable(self.id(bag)?, |id| {
+                self.contents[id].iter().map(|(inner, _)| *inner).collect()
+            }),
+            Scope::To(bag) => self.reachable(self.id(bag)?, |id| self.holders[id].clone()),
+        };
+        let mut dot = String::from("digraph bags {\n");
+        for (id, name) in self.names.iter().enumerate().filter(|(id, _)| kept[*id]) {
+            writeln!(dot, "    {:?};", name).unwrap();
+            for (inner, qty) in self.contents[id].iter().filter(|(inner, _)| kept[*inner]) {
+                writeln!(
+                    dot,
+                    "    {:?} -> {:?} [label={}];",
+                    name,
+                    self.name(*inner),
+                    qty
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        Some(dot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_input;
+    use super::*;
+
+    #[test]
+    fn test_to_dot() {
+        let graph = parse_input(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+muted yellow bags contain 2 shiny gold bags.
+shiny gold bags contain 3 faded blue bags.
+faded blue bags contain no other bags.",
+        )
+        .unwrap();
+        assert_eq!(
+            "digraph bags {
+    \"bright white\";
+    \"bright white\" -> \"shiny gold\" [label=1];
+    \"shiny gold\";
+    \"shiny gold\" -> \"faded blue\" [label=3];
+    \"faded blue\";
+}
+",
+            graph.to_dot(Scope::From("bright white")).unwrap()
+        );
+        let to = graph.to_dot(Scope::To("shiny gold")).unwrap();
+        assert!(to.contains("\"light red\" -> \"muted yellow\" [label=2];"));
+        assert!(!to.contains("faded blue"));
+        assert_eq!(12, graph.to_dot(Scope::All).unwrap().lines().count());
+        assert!(graph.to_dot(Scope::From("drab tan")).is_none());
+    }
+}
@@ -1,6 +1,12 @@
+mod dot;
+mod parser;
+mod validate;
+
 use std::{
     collections::{HashMap, HashSet},
-    env, fs,
+    env,
+    error::Error,
+    fs, process,
 };
 
 fn get_input_path() -> String {
@@ -20,119 +26,230 @@ struct Rule {
     pub constraints: Vec<Constraint>,
 }
 
-type Input = Vec<Rule>;
-type Output1 = usize;
-type Output2 = usize;
-
-fn parse_input(input: &str) -> Input {
-    input
-        .lines()
-        .map(|l| {
-            let tokens: Vec<&str> = l.split(" bags contain ").collect();
-            let container = tokens.get(0).unwrap().to_string();
-            let constraints = tokens.get(1).unwrap().trim_end_matches('.');
-            let constraints = match constraints {
-                "no other bags" => Vec::<Constraint>::new(),
-                _ => constraints
-                    .split(',')
-                    .map(|item| item.trim_end_matches('s').trim_end_matches("bag").trim())
-                    .map(|ctext| {
-                        let space_pos = ctext.find(' ').unwrap();
-                        let (quantity, bag) = ctext.split_at(space_pos);
-                        Constraint {
-                            quantity: quantity.parse().unwrap(),
-                            bag: bag.trim().to_string(),
-                        }
-                    })
-                    .collect(),
-            };
-            Rule {
-                container,
-                constraints,
-            }
-        })
-        .collect()
+type BagId = usize;
+
+/// Bag rules as a graph, bag names are interned so edges are plain indices.
+#[derive(Debug, Default)]
+struct BagGraph {
+    names: Vec<String>,
+    ids: HashMap<String, BagId>,
+    /// bags directly inside each bag, with their quantity
+    contents: Vec<Vec<(BagId, u16)>>,
+    /// bags directly holding each bag
+    holders: Vec<Vec<BagId>>,
+    /// number of rules defining each bag
+    rule_count: Vec<usize>,
 }
 
-const SHINY: &str = "shiny gold";
+impl BagGraph {
+    pub fn new(rules: &[Rule]) -> BagGraph {
+        let mut graph = BagGraph::default();
+        for rule in rules.iter() {
+            let container = graph.intern(&rule.container);
+            graph.rule_count[container] += 1;
+            for c in rule.constraints.iter() {
+                let bag = graph.intern(&c.bag);
+                graph.contents[container].push((bag, c.quantity));
+                graph.holders[bag].push(container);
+            }
+        }
+        graph
+    }
 
-fn solve_part_1(input: &Input) -> Output1 {
-    // build map of bags to list of bags that can contain it
-    let bag_holders = input
-        .iter()
-        .fold(HashMap::<&str, HashSet<&str>>::new(), |mut map, rule| {
-            rule.constraints.iter().map(|c| &c.bag).for_each(|bag| {
-                map.entry(bag)
-                    .and_modify(|bigger| {
-                        bigger.insert(&rule.container);
-                    })
-                    .or_insert_with(|| [rule.container.as_str()].iter().cloned().collect());
-            });
-            map
-        });
-    let mut bigger: HashSet<&str> = HashSet::new();
-    let mut bags_visitor: HashSet<&str> = HashSet::new();
-    bags_visitor.insert(SHINY);
-    // go through each visited bags until there isn't any bigger
-    while !bags_visitor.is_empty() {
-        let mut next: HashSet<&str> = HashSet::new();
-        // go through their possible container (bigger bags)
-        for b in bags_visitor.iter() {
-            if let Some(holders) = bag_holders.get(b) {
-                // mark them as to visit next
-                // add them to the bigger bags list
-                holders.iter().for_each(|h| {
-                    if !bigger.contains(h) {
-                        next.insert(h);
-                        bigger.insert(h);
-                    }
-                });
+    fn intern(&mut self, bag: &str) -> BagId {
+        if let Some(id) = self.ids.get(bag) {
+            return *id;
+        }
+        let id = self.names.len();
+        self.names.push(bag.to_string());
+        self.ids.insert(bag.to_string(), id);
+        self.contents.push(Vec::new());
+        self.holders.push(Vec::new());
+        self.rule_count.push(0);
+        id
+    }
+
+    pub fn id(&self, bag: &str) -> Option<BagId> {
+        self.ids.get(bag).copied()
+    }
+
+    pub fn name(&self, id: BagId) -> &str {
+        &self.names[id]
+    }
+
+    /// Every bag that eventually contains `bag`.
+    pub fn containers_of(&self, bag: &str) -> Option<HashSet<&str>> {
+        let mut bigger: HashSet<BagId> = HashSet::new();
+        let mut to_visit = vec![self.id(bag)?];
+        while let Some(visiting) = to_visit.pop() {
+            for holder in self.holders[visiting].iter() {
+                if bigger.insert(*holder) {
+                    to_visit.push(*holder);
+                }
             }
         }
-        // now visit the discovered bigger bags
-        bags_visitor = next;
+        Some(bigger.into_iter().map(|id| self.name(id)).collect())
     }
-    bigger.len()
-}
 
-fn solve_part_2(input: &Input) -> Output2 {
-    let bag_constraints: HashMap<&str, &[Constraint]> = input
-        .iter()
-        .map(|rule| (rule.container.as_str(), rule.constraints.as_slice()))
-        .collect();
-    let mut contained: usize = 0;
-    let mut bags_visitor: HashMap<&str, usize> = HashMap::new();
-    bags_visitor.insert(SHINY, 1);
-    // go through each bags to look into until there is no content
-    while !bags_visitor.is_empty() {
-        let mut next: HashMap<&str, usize> = HashMap::new();
-        // get each visited constraints
-        for (visiting, qty) in bags_visitor.iter() {
-            if let Some(constraints) = bag_constraints.get(visiting) {
-                // count additional bags
-                // also mark the new content as to be visited next
-                for c in constraints.iter() {
-                    let inside = qty * c.quantity as usize;
-                    contained += inside;
-                    next.entry(&c.bag)
-                        .and_modify(|count| *count += inside)
-                        .or_insert(inside);
+    /// Bags directly inside `bag`, with their quantity.
+    pub fn direct_contents(&self, bag: &str) -> Option<Vec<(&str, u16)>> {
+        let id = self.id(bag)?;
+        Some(
+            self.contents[id]
+                .iter()
+                .map(|(inner, qty)| (self.name(*inner), *qty))
+                .collect(),
+        )
+    }
+
+    /// Count of bags inside `bag`, at any depth. Fails on unknown bags,
+    /// cycles and counts not fitting in a `u64`.
+    pub fn total_contents(&self, bag: &str) -> Result<u64, Box<dyn Error>> {
+        let id = self.id(bag).ok_or_else(|| format!("Unknown bag {}", bag))?;
+        let mut memo = vec![Count::Unknown; self.names.len()];
+        self.count_inside(id, &mut memo)
+    }
+
+    /// Post-order walk of the contents from `id` with an explicit stack of
+    /// bags, the index of their next inner bag and their count so far. A bag
+    /// is counted once all its inner bags are known.
+    fn count_inside(&self, id: BagId, memo: &mut [Count]) -> Result<u64, Box<dyn Error>> {
+        if let Count::Known(count) = memo[id] {
+            return Ok(count);
+        }
+        memo[id] = Count::InProgress;
+        let mut stack: Vec<(BagId, usize, u64)> = vec![(id, 0, 0)];
+        while let Some((bag, next, count)) = stack.last_mut() {
+            let (inner, qty) = match self.contents[*bag].get(*next) {
+                Some(inner) => *inner,
+                None => {
+                    memo[*bag] = Count::Known(*count);
+                    stack.pop();
+                    continue;
+                }
+            };
+            match memo[inner] {
+                Count::Known(inner_count) => {
+                    // each inner bag counts for itself and its own contents
+                    let overflow = || format!("Too many bags inside {}", self.name(*bag));
+                    let added = inner_count
+                        .checked_add(1)
+                        .and_then(|c| c.checked_mul(qty as u64))
+                        .ok_or_else(overflow)?;
+                    *count = count.checked_add(added).ok_or_else(overflow)?;
+                    *next += 1;
+                }
+                Count::InProgress => {
+                    return Err(format!("Bag {} contains itself", self.name(inner)).into());
+                }
+                Count::Unknown => {
+                    memo[inner] = Count::InProgress;
+                    stack.push((inner, 0, 0));
                 }
             }
         }
-        bags_visitor = next;
+        match memo[id] {
+            Count::Known(count) => Ok(count),
+            _ => unreachable!(),
+        }
     }
-    contained
+}
+
+#[derive(Clone, Copy)]
+enum Count {
+    Unknown,
+    InProgress,
+    Known(u64),
+}
+
+type Input = BagGraph;
+type Output1 = usize;
+type Output2 = u64;
+
+fn parse_input(input: &str) -> Result<Input, parser::ParseError> {
+    Ok(BagGraph::new(&parser::parse_rules(input)?))
+}
+
+const SHINY: &str = "shiny gold";
+
+/// Rule sets without a shiny gold bag have none around it nor inside it.
+fn solve_part_1(input: &Input) -> Output1 {
+    input.containers_of(SHINY).map_or(0, |bags| bags.len())
+}
+
+fn solve_part_2(input: &Input) -> Result<Output2, Box<dyn Error>> {
+    match input.id(SHINY) {
+        Some(_) => input.total_contents(SHINY),
+        None => Ok(0),
+    }
+}
+
+/// What `bag` holds and what holds it.
+fn describe_bag(input: &Input, bag: &str) -> Result<(), Box<dyn Error>> {
+    let direct = input
+        .direct_contents(bag)
+        .ok_or_else(|| format!("Unknown bag {}", bag))?;
+    println!("{} directly contains {:?}", bag, direct);
+    println!("{} contains {:?} bags", bag, input.total_contents(bag)?);
+    println!(
+        "{} can be in {:?} bags",
+        bag,
+        input.containers_of(bag).map_or(0, |bags| bags.len())
+    );
+    Ok(())
+}
+
+fn exit_on_error<T>(result: Result<T, Box<dyn Error>>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    })
+}
+
+/// Looks for a `--dot`, `--dot-from=<bag>` or `--dot-to=<bag>` option.
+fn get_dot_scope(args: &[String]) -> Option<dot::Scope<'_>> {
+    args.iter().find_map(|arg| {
+        if arg == "--dot" {
+            Some(dot::Scope::All)
+        } else if let Some(bag) = arg.strip_prefix("--dot-from=") {
+            Some(dot::Scope::From(bag))
+        } else {
+            arg.strip_prefix("--dot-to=").map(dot::Scope::To)
+        }
+    })
 }
 
 fn main() {
     let input_path = get_input_path();
     let raw_input = fs::read_to_string(input_path).unwrap();
-    let input = parse_input(&raw_input);
+    let input = parse_input(&raw_input).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+    let issues = input.validate();
+    issues.iter().for_each(|issue| eprintln!("{}", issue));
+    let args: Vec<String> = env::args().collect();
+    if let Some(scope) = get_dot_scope(&args) {
+        let dot = input
+            .to_dot(scope)
+            .ok_or_else(|| "Unknown bag in the dot scope".into());
+        print!("{}", exit_on_error(dot));
+        return;
+    }
+    if issues
+        .iter()
+        .any(|issue| matches!(issue, validate::Issue::Cycle(_)))
+    {
+        process::exit(1);
+    }
     let part_1_result = solve_part_1(&input);
     println!("Part 1: {:?}", part_1_result);
-    let part_2_result = solve_part_2(&input);
+    let part_2_result = exit_on_error(solve_part_2(&input));
     println!("Part 2: {:?}", part_2_result);
+    // any other bag may be queried after the input path
+    if let Some(bag) = args.get(2) {
+        exit_on_error(describe_bag(&input, bag));
+    }
 }
 
 #[cfg(test)]
@@ -150,21 +267,97 @@ dotted black bags contain no other bags.";
 
     #[test]
     fn test_parse() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parser::parse_rules(EXAMPLE_INPUT).unwrap();
         dbg!(input);
     }
 
     #[test]
     fn test_part1_example() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let res = solve_part_1(&input);
         assert_eq!(4, res);
     }
 
     #[test]
     fn test_part2_example() {
-        let input = parse_input(EXAMPLE_INPUT);
-        let res = solve_part_2(&input);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let res = solve_part_2(&input).unwrap();
         assert_eq!(32, res);
     }
+
+    #[test]
+    fn test_without_shiny_gold() {
+        let input = parse_input("dark red bags contain no other bags.").unwrap();
+        assert_eq!(0, solve_part_1(&input));
+        assert_eq!(0, solve_part_2(&input).unwrap());
+        assert!(describe_bag(&input, "shiny gold").is_err());
+    }
+
+    #[test]
+    fn test_graph_queries() {
+        let graph = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut containers: Vec<&str> = graph
+            .containers_of("dark olive")
+            .unwrap()
+            .into_iter()
+            .collect();
+        containers.sort_unstable();
+        assert_eq!(
+            vec![
+                "bright white",
+                "dark orange",
+                "light red",
+                "muted yellow",
+                "shiny gold"
+            ],
+            containers
+        );
+        assert_eq!(
+            vec![("faded blue", 3), ("dotted black", 4)],
+            graph.direct_contents("dark olive").unwrap()
+        );
+        assert_eq!(7, graph.total_contents("dark olive").unwrap());
+        assert_eq!(0, graph.total_contents("faded blue").unwrap());
+        assert!(graph.containers_of("light red").unwrap().is_empty());
+        assert!(graph.total_contents("striped purple").is_err());
+    }
+
+    #[test]
+    fn test_total_contents_overflow() {
+        // colors are "dim a", "dim b"... each holding 65535 of the next one
+        let color = |n: usize| format!("dim {}", (b'a' + n as u8) as char);
+        let chain = |depth: usize| {
+            let mut rules: String = (0..depth)
+                .map(|n| format!("{} bags contain 65535 {} bags.\n", color(n), color(n + 1)))
+                .collect();
+            rules.push_str(&format!("{} bags contain no other bags.", color(depth)));
+            parse_input(&rules).unwrap()
+        };
+        assert_eq!(65535 * 65536, chain(2).total_contents("dim a").unwrap());
+        assert!(chain(6).total_contents("dim a").is_err());
+        let cycle =
+            parse_input("a b bags contain 1 c d bag.\nc d bags contain 2 a b bags.").unwrap();
+        assert!(cycle.total_contents("a b").is_err());
+    }
+
+    #[test]
+    fn test_total_contents_deep() {
+        // "bag 0" holds "bag 1" ... down to an empty one
+        let depth = 200_000;
+        let rules: Vec<Rule> = (0..depth)
+            .map(|n| Rule {
+                container: format!("bag {}", n),
+                constraints: vec![Constraint {
+                    bag: format!("bag {}", n + 1),
+                    quantity: 1,
+                }],
+            })
+            .collect();
+        let graph = BagGraph::new(&rules);
+        assert_eq!(depth as u64, graph.total_contents("bag 0").unwrap());
+        assert_eq!(
+            1,
+            graph.total_contents(&format!("bag {}", depth - 1)).unwrap()
+        );
+    }
 }
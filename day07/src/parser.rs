@@ -0,0 +1,287 @@
+//! Parser for rule sentences:
+//!
+//! ```text
+//! rule     := color "bags" "contain" contents "."
+//! contents := "no" "other" "bags" | item ("," item)*
+//! item     := number color ("bag" | "bags")
+//! color    := word+
+//! ```
+//!
+//! Words may be separated by any amount of whitespace.
+
+use super::{Constraint, Rule};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Word,
+    Number,
+    Comma,
+    Period,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    /// 1 based char column
+    column: usize,
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token<'_>>, usize> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = line.char_indices().enumerate().peekable();
+    while let Some((column, (start, c))) = chars.next() {
+        let kind = if c.is_whitespace() {
+            continue;
+        } else if c == ',' {
+            TokenKind::Comma
+        } else if c == '.' {
+            TokenKind::Period
+        } else if c.is_ascii_digit() {
+            TokenKind::Number
+        } else if c.is_alphabetic() {
+            TokenKind::Word
+        } else {
+            return Err(column + 1);
+        };
+        let mut end = start + c.len_utf8();
+        if kind == TokenKind::Number || kind == TokenKind::Word {
+            let same = |c: &char| match kind {
+                TokenKind::Number => c.is_ascii_digit(),
+                _ => c.is_alphabetic() || *c == '-' || *c == '\'',
+            };
+            while let Some((_, (pos, next))) = chars.peek().copied() {
+                if !same(&next) {
+                    break;
+                }
+                end = pos + next.len_utf8();
+                chars.next();
+            }
+        }
+        tokens.push(Token {
+            kind,
+            text: &line[start..end],
+            column: column + 1,
+        });
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    idx: usize,
+    line: usize,
+    /// column right after the last char, reported when tokens run out
+    end_column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: String) -> ParseError {
+        let column = self
+            .tokens
+            .get(self.idx)
+            .map_or(self.end_column, |t| t.column);
+        ParseError {
+            line: self.line,
+            column,
+            message,
+        }
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.idx)
+    }
+
+    fn describe(&self) -> String {
+        self.peek()
+            .map_or("end of line".to_string(), |t| format!("'{}'", t.text))
+    }
+
+    fn next_kind(&mut self, kind: TokenKind, expected: &str) -> Result<&'a str, ParseError> {
+        match self.peek() {
+            Some(t) if t.kind == kind => {
+                let text = t.text;
+                self.idx += 1;
+                Ok(text)
+            }
+            _ => Err(self.error(format!("expected {}, found {}", expected, self.describe()))),
+        }
+    }
+
+    fn keyword(&mut self, words: &[&str]) -> Result<&'a str, ParseError> {
+        let expected = words
+            .iter()
+            .map(|w| format!("'{}'", w))
+            .collect::<Vec<String>>()
+            .join(" or ");
+        match self.peek() {
+            Some(t) if t.kind == TokenKind::Word && words.contains(&t.text) => {
+                let text = t.text;
+                self.idx += 1;
+                Ok(text)
+            }
+            _ => Err(self.error(format!("expected {}, found {}", expected, self.describe()))),
+        }
+    }
+
+    fn is_keyword(&self, word: &str) -> bool {
+        matches!(self.peek(), Some(t) if t.kind == TokenKind::Word && t.text == word)
+    }
+
+    /// Words up to the following "bag" or "bags".
+    fn color(&mut self) -> Result<String, ParseError> {
+        let mut words: Vec<&str> = Vec::new();
+        while let Some(t) = self.peek() {
+            if t.kind != TokenKind::Word || t.text == "bag" || t.text == "bags" {
+                break;
+            }
+            words.push(t.text);
+            self.idx += 1;
+        }
+        if words.is_empty() {
+            return Err(self.error(format!("expected a color, found {}", self.describe())));
+        }
+        Ok(words.join(" "))
+    }
+
+    fn item(&mut self) -> Result<Constraint, ParseError> {
+        let column = self.peek().map_or(self.end_column, |t| t.column);
+        let quantity = self.next_kind(TokenKind::Number, "a quantity")?;
+        let quantity = quantity.parse().map_err(|_| ParseError {
+            line: self.line,
+            column,
+            message: format!("quantity {} is too large", quantity),
+        })?;
+        let bag = self.color()?;
+        self.keyword(&["bag", "bags"])?;
+        Ok(Constraint { bag, quantity })
+    }
+
+    fn rule(&mut self) -> Result<Rule, ParseError> {
+        let container = self.color()?;
+        self.keyword(&["bags"])?;
+        self.keyword(&["contain"])?;
+        let mut constraints: Vec<Constraint> = Vec::new();
+        if self.is_keyword("no") {
+            self.keyword(&["no"])?;
+            self.keyword(&["other"])?;
+            self.keyword(&["bags"])?;
+        } else {
+            constraints.push(self.item()?);
+            while self.peek().map(|t| t.kind) == Some(TokenKind::Comma) {
+                self.idx += 1;
+                constraints.push(self.item()?);
+            }
+        }
+        self.next_kind(TokenKind::Period, "',' or '.'")?;
+        if self.peek().is_some() {
+            return Err(self.error(format!("expected end of line, found {}", self.describe())));
+        }
+        Ok(Rule {
+            container,
+            constraints,
+        })
+    }
+}
+
+/// Parses one rule, `line` is the 1 based line number used in errors.
+pub fn parse_rule(text: &str, line: usize) -> Result<Rule, ParseError> {
+    let tokens = tokenize(text).map_err(|column| ParseError {
+        line,
+        column,
+        message: "unexpected character".to_string(),
+    })?;
+    let mut parser = Parser {
+        tokens,
+        idx: 0,
+        line,
+        end_column: text.chars().count() + 1,
+    };
+    parser.rule()
+}
+
+/// Parses every non blank line of `input`.
+pub fn parse_rules(input: &str) -> Result<Vec<Rule>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(idx, l)| parse_rule(l, idx + 1))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = parse_rule(
+            "  dark glass   bags contain 1 shiny  gold bag,2 dark glass bags .",
+            1,
+        )
+        .unwrap();
+        assert_eq!("dark glass", rule.container);
+        assert_eq!(2, rule.constraints.len());
+        assert_eq!("shiny gold", rule.constraints[0].bag);
+        assert_eq!(1, rule.constraints[0].quantity);
+        assert_eq!("dark glass", rule.constraints[1].bag);
+        assert_eq!(2, rule.constraints[1].quantity);
+        let rule = parse_rule("faded bass bags contain no other bags.", 1).unwrap();
+        assert_eq!("faded bass", rule.container);
+        assert!(rule.constraints.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = |text: &str| parse_rule(text, 3).unwrap_err();
+        assert_eq!(
+            ParseError {
+                line: 3,
+                column: 24,
+                message: "expected a quantity, found 'shiny'".to_string()
+            },
+            err("light red bags contain shiny gold bags.")
+        );
+        assert_eq!(41, err("light red bags contain 2 shiny gold bags").column);
+        assert_eq!(11, err("light red bag contain no other bags.").column);
+        assert_eq!(
+            "expected 'bag' or 'bags', found '.'",
+            err("light red bags contain 2 shiny gold bagz.").message
+        );
+        assert_eq!(23, err("light red bags contain; no other bags.").column);
+        assert_eq!(
+            24,
+            err("light red bags contain 99999 shiny gold bags.").column
+        );
+        assert_eq!(
+            "line 2, column 2: expected a color, found 'bags'",
+            parse_rules("\n bags contain no other bags.")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
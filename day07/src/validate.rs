@@ -0,0 +1,157 @@
+use super::{BagGraph, BagId};
+use std::fmt::{self, Display};
+
+/// Mistake found in a rule set.
+#[derive(Debug, PartialEq)]
+pub enum Issue<'g> {
+    /// bags containing themselves, the first bag is repeated at the end
+    Cycle(Vec<&'g str>),
+    /// bag held by others but without a rule of its own
+    Undefined(&'g str),
+    /// bag with several rules, their contents are merged
+    Duplicate(&'g str),
+    /// bag with a rule but neither holding nor held by any other bag
+    Unreachable(&'g str),
+}
+
+impl<'g> Display for Issue<'g> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::Cycle(path) => write!(f, "cycle: {}", path.join(" -> ")),
+            Issue::Undefined(bag) => write!(f, "undefined bag: {}", bag),
+            Issue::Duplicate(bag) => write!(f, "duplicate rule for: {}", bag),
+            Issue::Unreachable(bag) => write!(f, "unreachable bag: {}", bag),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    InProgress,
+    Done,
+}
+
+impl BagGraph {
+    pub fn validate(&self) -> Vec<Issue<'_>> {
+        let mut issues: Vec<Issue> = Vec::new();
+        for (id, name) in self.names.iter().enumerate() {
+            match self.rule_count[id] {
+                0 => issues.push(Issue::Undefined(name)),
+                1 => (),
+                _ => issues.push(Issue::Duplicate(name)),
+            }
+            if self.rule_count[id] > 0
+                && self.contents[id].is_empty()
+                && self.holders[id].is_empty()
+            {
+                issues.push(Issue::Unreachable(name));
+            }
+        }
+        let mut visits = vec![Visit::New; self.names.len()];
+        let mut path: Vec<BagId> = Vec::new();
+        for id in 0..self.names.len() {
+            self.find_cycles(id, &mut visits, &mut path, &mut issues);
+        }
+        issues
+    }
+
+    /// Depth first walk of the contents, any edge back to a bag of the
+    /// current path closes a cycle.
+    ///
+    /// The walk keeps its own stack of bags with the index of the next inner
+    /// bag to visit, so deep nestings don't overflow the call stack.
+    fn find_cycles<'g>(
+        &'g self,
+        id: BagId,
+        visits: &mut [Visit],
+        path: &mut Vec<BagId>,
+        issues: &mut Vec<Issue<'g>>,
+    ) {
+        if visits[id] != Visit::New {
+            return;
+        }
+        let mut stack: Vec<(BagId, usize)> = vec![(id, 0)];
+        visits[id] = Visit::InProgress;
+        path.push(id);
+        while let Some((id, next)) = stack.last_mut() {
+            let inner = match self.contents[*id].get(*next) {
+                Some((inner, _)) => inner,
+                None => {
+                    visits[*id] = Visit::Done;
+                    stack.pop();
+                    path.pop();
+                    continue;
+                }
+            };
+            *next += 1;
+            match visits[*inner] {
+                Visit::New => {
+                    visits[*inner] = Visit::InProgress;
+                    stack.push((*inner, 0));
+                    path.push(*inner);
+                }
+                Visit::InProgress => {
+                    let start = path.iter().position(|bag| bag == inner).unwrap();
+                    let mut cycle: Vec<&str> =
+                        path[start..].iter().map(|bag| self.name(*bag)).collect();
+                    cycle.push(self.name(*inner));
+                    issues.push(Issue::Cycle(cycle));
+                }
+                Visit::Done => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse_input, Constraint, Rule};
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let graph = parse_input(
+            "light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+shiny gold bags contain 3 light red bags.
+muted yellow bags contain no other bags.
+muted yellow bags contain 4 faded blue bags.
+dotted black bags contain no other bags.",
+        )
+        .unwrap();
+        let issues = graph.validate();
+        assert_eq!(
+            vec![
+                Issue::Duplicate("muted yellow"),
+                Issue::Undefined("faded blue"),
+                Issue::Unreachable("dotted black"),
+                Issue::Cycle(vec!["light red", "bright white", "shiny gold", "light red"]),
+            ],
+            issues
+        );
+    }
+
+    #[test]
+    fn test_deep_cycle() {
+        // bag 0 holds bag 1 ... which holds bag 0 again
+        let depth = 200_000;
+        let rules: Vec<Rule> = (0..depth)
+            .map(|n| Rule {
+                container: format!("bag {}", n),
+                constraints: vec![Constraint {
+                    bag: format!("bag {}", (n + 1) % depth),
+                    quantity: 1,
+                }],
+            })
+            .collect();
+        let graph = BagGraph::new(&rules);
+        match graph.validate().as_slice() {
+            [Issue::Cycle(cycle)] => {
+                assert_eq!(depth + 1, cycle.len());
+                assert_eq!(Some(&"bag 0"), cycle.last());
+            }
+            issues => panic!("unexpected issues {:?}", issues),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Assembly language for the boot code:
+//!
+//! ```text
+//! ; comments start with ';'
+//! start:  acc +1      ; labels end with ':'
+//!         jnz a start ; jump offsets may name a label
+//! end:
+//! ```
+//!
+//! The puzzle input, with numeric offsets only, is valid assembly.
+
+use super::machine::{Instruction, Operand, OperandKind, Program, INSTRUCTION_SET};
+use std::{collections::HashMap, convert::TryFrom, error::Error, fmt::Write};
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Splits the labels defined at the start of `line` from the instruction.
+fn split_labels(line: &str) -> (Vec<&str>, &str) {
+    let mut labels: Vec<&str> = Vec::new();
+    let mut rest = line.trim();
+    while let Some((label, after)) = rest.split_once(':') {
+        if !is_label(label.trim_end()) {
+            break;
+        }
+        labels.push(label.trim_end());
+        rest = after.trim_start();
+    }
+    (labels, rest)
+}
+
+/// Replaces label names used as jump offsets in the instruction at `addr`.
+fn resolve(text: &str, addr: usize, labels: &HashMap<&str, usize>) -> Result<String, String> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let kinds = INSTRUCTION_SET
+        .iter()
+        .find(|def| def.mnemonic == tokens[0])
+        .map_or(&[][..], |def| def.operands);
+    let mut resolved = vec![tokens[0].to_string()];
+    for (idx, token) in tokens.iter().enumerate().skip(1) {
+        if kinds.get(idx - 1) == Some(&OperandKind::Offset) && is_label(token) {
+            let target = labels
+                .get(token)
+                .ok_or_else(|| format!("Unknown label {}", token))?;
+            resolved.push(format!("{:+}", *target as i64 - addr as i64));
+        } else {
+            resolved.push(token.to_string());
+        }
+    }
+    Ok(resolved.join(" "))
+}
+
+pub fn assemble(input: &str) -> Result<Program, Box<dyn Error>> {
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    // line number and text of each instruction
+    let mut lines: Vec<(usize, &str)> = Vec::new();
+    for (idx, line) in input.lines().enumerate() {
+        let code = line.split(';').next().unwrap();
+        let (names, inst) = split_labels(code);
+        for name in names {
+            if labels.insert(name, lines.len()).is_some() {
+                return Err(format!("line {}: Duplicate label {}", idx + 1, name).into());
+            }
+        }
+        if !inst.is_empty() {
+            lines.push((idx + 1, inst));
+        }
+    }
+    lines
+        .iter()
+        .enumerate()
+        .map(|(addr, (line, text))| {
+            resolve(text, addr, &labels)
+                .and_then(|text| text.parse::<Instruction>().map_err(|err| err.to_string()))
+                .map_err(|err| format!("line {}: {}", line, err).into())
+        })
+        .collect()
+}
+
+/// Jump target of an offset operand of the instruction at `addr`.
+fn target(addr: usize, kind: &OperandKind, arg: &Operand) -> Option<usize> {
+    match (kind, arg) {
+        (OperandKind::Offset, Operand::Imm(off)) => usize::try_from(addr as i64 + *off as i64).ok(),
+        _ => None,
+    }
+}
+
+/// Assembly of `prog`, every jump target within the program (or right after
+/// it) gets a label `L1`, `L2`, ... numbered by address.
+pub fn disassemble(prog: &Program) -> String {
+    let operands = |inst: &Instruction| {
+        inst.op
+            .def()
+            .operands
+            .iter()
+            .zip(inst.operands().to_vec())
+            .collect::<Vec<_>>()
+    };
+    let mut targets: Vec<usize> = prog
+        .iter()
+        .enumerate()
+        .flat_map(|(addr, inst)| {
+            operands(inst)
+                .into_iter()
+                .filter_map(move |(kind, arg)| target(addr, kind, &arg))
+        })
+        .filter(|t| *t <= prog.len())
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+    let name = |addr: usize| {
+        targets
+            .binary_search(&addr)
+            .ok()
+            .map(|idx| format!("L{}", idx + 1))
+    };
+    let mut text = String::new();
+    for (addr, inst) in prog.iter().enumerate() {
+        if let Some(label) = name(addr) {
+            writeln!(text, "{}:", label).unwrap();
+        }
+        write!(text, "    {}", inst.op.def().mnemonic).unwrap();
+        for (kind, arg) in operands(inst) {
+            match target(addr, kind, &arg).and_then(name) {
+                Some(label) => write!(text, " {}", label).unwrap(),
+                None => write!(text, " {}", arg).unwrap(),
+            }
+        }
+        text.push('\n');
+    }
+    if let Some(label) = name(prog.len()) {
+        writeln!(text, "{}:", label).unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let prog = assemble(
+            "; countdown from 3
+        set a +3
+loop:   jz a end   ; done
+        add a -1
+        out a
+        jmp loop
+end:",
+        )
+        .unwrap();
+        let printed: Vec<String> = prog.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            vec!["set a +3", "jz a +4", "add a -1", "out a", "jmp -3"],
+            printed
+        );
+        assert_eq!(
+            "line 2: Unknown label nowhere",
+            assemble("nop +0\njmp nowhere").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "line 3: Duplicate label a",
+            assemble("a: nop +0\n\na:").unwrap_err().to_string()
+        );
+        assert!(assemble("a: b: jmp a\nnop b").is_err());
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let prog = assemble(
+            "nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\njmp +2\njmp -20",
+        )
+        .unwrap();
+        let text = disassemble(&prog);
+        assert_eq!(
+            "    nop +0
+L1:
+    acc +1
+    jmp L3
+L2:
+    acc +3
+    jmp L1
+    acc -99
+L3:
+    acc +1
+    jmp L2
+    jmp L4
+    jmp -20
+L4:
+",
+            text
+        );
+        assert_eq!(prog, assemble(&text).unwrap());
+    }
+}
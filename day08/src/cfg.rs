@@ -0,0 +1,178 @@
+//! Static control-flow graph of a program, used to find the instructions
+//! whose flip lets the program terminate without running it.
+//!
+//! Node `prog.len()` is the end of the program: reaching it is a clean
+//! termination. Conditional jumps have both successors, so for programs using
+//! them "terminating" means "may terminate".
+
+use super::machine::{Instruction, Operand, OperandKind, Program};
+use std::collections::VecDeque;
+
+/// A jmp or nop which, once flipped, leads to the end of the program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Flip {
+    pub idx: usize,
+    pub inst: Instruction,
+}
+
+pub struct Cfg {
+    succ: Vec<Vec<usize>>,
+    pred: Vec<Vec<usize>>,
+}
+
+fn target(idx: usize, off: Operand, len: usize) -> Option<usize> {
+    let off = match off {
+        Operand::Imm(off) => off as isize,
+        Operand::Reg(_) => return None,
+    };
+    (idx as isize)
+        .checked_add(off)
+        .filter(|t| *t >= 0 && *t as usize <= len)
+        .map(|t| t as usize)
+}
+
+/// Statically known successors of the instruction `idx` when it is `inst`,
+/// jumps out of the program are dropped.
+fn successors(inst: &Instruction, idx: usize, len: usize) -> Vec<usize> {
+    let def = inst.op.def();
+    let mut succ: Vec<usize> = Vec::new();
+    if def.falls_through {
+        succ.push(idx + 1);
+    }
+    for (kind, arg) in def.operands.iter().zip(inst.operands()) {
+        if *kind == OperandKind::Offset {
+            succ.extend(target(idx, *arg, len).filter(|t| !succ.contains(t)));
+        }
+    }
+    succ
+}
+
+fn flipped(inst: &Instruction) -> Option<Instruction> {
+    let op = inst.op.def().flip?;
+    Some(Instruction { op, ..*inst })
+}
+
+impl Cfg {
+    pub fn new(prog: &Program) -> Cfg {
+        let len = prog.len();
+        let succ: Vec<Vec<usize>> = prog
+            .iter()
+            .enumerate()
+            .map(|(idx, inst)| successors(inst, idx, len))
+            .chain(std::iter::once(Vec::new()))
+            .collect();
+        let mut pred: Vec<Vec<usize>> = vec![Vec::new(); len + 1];
+        for (idx, targets) in succ.iter().enumerate() {
+            for t in targets {
+                pred[*t].push(idx);
+            }
+        }
+        Cfg { succ, pred }
+    }
+
+    fn end(&self) -> usize {
+        self.succ.len() - 1
+    }
+
+    /// Nodes reachable from `starts` following `edges`.
+    fn reach(edges: &[Vec<usize>], starts: &[usize]) -> Vec<bool> {
+        let mut seen = vec![false; edges.len()];
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for s in starts {
+            seen[*s] = true;
+            queue.push_back(*s);
+        }
+        while let Some(node) = queue.pop_front() {
+            for next in edges[node].iter() {
+                if !seen[*next] {
+                    seen[*next] = true;
+                    queue.push_back(*next);
+                }
+            }
+        }
+        seen
+    }
+
+    /// Instructions run when starting at 0.
+    pub fn reachable(&self) -> Vec<bool> {
+        Self::reach(&self.succ, &[0])
+    }
+
+    /// Instructions from which the end can be reached, `hlt` included.
+    pub fn terminating(&self, prog: &Program) -> Vec<bool> {
+        let mut starts = vec![self.end()];
+        starts.extend((0..prog.len()).filter(|idx| prog[*idx].op.def().halts()));
+        Self::reach(&self.pred, &starts)
+    }
+
+    /// Reachable jmp and nop whose flipped successor terminates.
+    ///
+    /// If the original program loops, a flipped instruction can't be on the
+    /// path to the end of the original graph, so the flip alone repairs it.
+    pub fn candidates(&self, prog: &Program) -> Vec<Flip> {
+        let reachable = self.reachable();
+        let terminating = self.terminating(prog);
+        prog.iter()
+            .enumerate()
+            .filter(|(idx, _)| reachable[*idx] && !terminating[*idx])
+            .filter_map(|(idx, inst)| {
+                let inst = flipped(inst)?;
+                successors(&inst, idx, prog.len())
+                    .iter()
+                    .any(|s| terminating[*s])
+                    .then_some(Flip { idx, inst })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_input;
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let prog =
+            parse_input("nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6")
+                .unwrap();
+        let cfg = Cfg::new(&prog);
+        let reachable = cfg.reachable();
+        let reachable: Vec<usize> = (0..prog.len()).filter(|i| reachable[*i]).collect();
+        assert_eq!(vec![0, 1, 2, 3, 4, 6, 7], reachable);
+        let flips: Vec<usize> = cfg.candidates(&prog).iter().map(|f| f.idx).collect();
+        assert_eq!(vec![7], flips);
+        // nop +3 and jmp -2 both lead to the end once flipped here
+        let prog = parse_input("nop +3\njmp +1\njmp -2\nacc +1").unwrap();
+        let flips: Vec<usize> = Cfg::new(&prog)
+            .candidates(&prog)
+            .iter()
+            .map(|f| f.idx)
+            .collect();
+        assert_eq!(vec![0, 2], flips);
+    }
+
+    #[test]
+    fn test_successors() {
+        let succ = |inst: &str, idx: usize| successors(&inst.parse().unwrap(), idx, 4);
+        assert_eq!(vec![2, 3], succ("jz a +2", 1));
+        assert_eq!(vec![1], succ("jnz a +1", 0));
+        assert_eq!(vec![0], succ("jmp -3", 3));
+        assert!(succ("jmp -2", 1).is_empty());
+        assert!(succ("hlt", 1).is_empty());
+        assert_eq!(vec![2], succ("out a", 1));
+    }
+
+    #[test]
+    fn test_large_program() {
+        // a chain of jumps to the end, except one jumping back to the start
+        let n = 200_000;
+        let mut text: Vec<String> = (0..n).map(|_| "jmp +1".to_string()).collect();
+        text[n / 2] = format!("jmp -{}", n / 2);
+        let prog = parse_input(&text.join("\n")).unwrap();
+        let flips = Cfg::new(&prog).candidates(&prog);
+        assert_eq!(1, flips.len());
+        assert_eq!(n / 2, flips[0].idx);
+        assert_eq!("nop -100000", flips[0].inst.to_string());
+    }
+}
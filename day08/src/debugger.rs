@@ -0,0 +1,328 @@
+//! Interactive debugger around a `Machine`, started with `--debug`.
+
+use super::machine::{Machine, ProgramState};
+use super::trace::Trace;
+use std::io::{self, BufRead, Write};
+
+/// Steps `continue` runs before giving up on reaching a breakpoint.
+const MAX_CONTINUE: usize = 1_000_000;
+
+/// Executed instructions kept for `history` and `back`.
+const HISTORY_LEN: usize = 10_000;
+
+const HELP: &str = "commands:
+  s, step [n]         execute n instructions (default 1)
+  c, continue         run until a breakpoint, termination or input wait
+  b, break <idx>      break before running instruction idx
+  b, break acc=<v>    break when the accumulator becomes v
+  b, break loop       break before running an instruction a second time
+  d, delete <n>       delete breakpoint number n
+  breakpoints         list breakpoints
+  w, watch            toggle printing accumulator changes
+  l, list [n]         disassemble n instructions around iptr (default 3)
+  h, history [n]      show the last n executed instructions (default 10)
+  back [n]            undo n of the last 10000 instructions
+  r, regs             show registers
+  i, input <v>...     queue input values
+  q, quit             leave the debugger
+an empty line repeats the last command";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Breakpoint {
+    Addr(usize),
+    Acc(i32),
+    Loop,
+}
+
+pub struct Debugger<'a> {
+    machine: Machine<'a>,
+    breakpoints: Vec<Breakpoint>,
+    watch: bool,
+    /// times each instruction ran
+    visits: Vec<usize>,
+    last_command: String,
+    /// steps `continue` runs before giving up
+    max_continue: usize,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(mut machine: Machine<'a>) -> Debugger<'a> {
+        machine.record();
+        machine.record_limit = Some(HISTORY_LEN);
+        let visits = vec![0; machine.program().len()];
+        Debugger {
+            machine,
+            breakpoints: Vec::new(),
+            watch: false,
+            visits,
+            last_command: "step".to_string(),
+            max_continue: MAX_CONTINUE,
+        }
+    }
+
+    /// Reads commands from `input` until `quit` or end of input.
+    pub fn repl(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        self.list(3, out)?;
+        write!(out, "(dbg) ")?;
+        out.flush()?;
+        for line in input.lines() {
+            let line = line?;
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                cmd => cmd.to_string(),
+            };
+            if !self.command(&line, out)? {
+                return Ok(());
+            }
+            self.last_command = line;
+            write!(out, "(dbg) ")?;
+            out.flush()?;
+        }
+        writeln!(out)
+    }
+
+    /// Runs one command, returns false when the debugger should stop.
+    fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let mut tokens = line.split_whitespace();
+        let cmd = tokens.next().unwrap_or_default();
+        let args: Vec<&str> = tokens.collect();
+        let count = |default: usize| args.first().and_then(|n| n.parse().ok()).unwrap_or(default);
+        match cmd {
+            "s" | "step" => {
+                for _ in 0..count(1) {
+                    if self.step(out)? != ProgramState::Running {
+                        break;
+                    }
+                }
+                self.list(0, out)?;
+            }
+            "c" | "continue" => self.cont(out)?,
+            "b" | "break" => match args.first().map(|arg| parse_breakpoint(arg)) {
+                Some(Some(bp)) => {
+                    self.breakpoints.push(bp);
+                    writeln!(out, "breakpoint {}: {:?}", self.breakpoints.len(), bp)?;
+                }
+                _ => writeln!(out, "usage: break <idx> | acc=<v> | loop")?,
+            },
+            "d" | "delete" => match count(0) {
+                n if n >= 1 && n <= self.breakpoints.len() => {
+                    self.breakpoints.remove(n - 1);
+                }
+                _ => writeln!(out, "no such breakpoint")?,
+            },
+            "breakpoints" => {
+                for (idx, bp) in self.breakpoints.iter().enumerate() {
+                    writeln!(out, "{}: {:?}", idx + 1, bp)?;
+                }
+            }
+            "w" | "watch" => {
+                self.watch = !self.watch;
+                writeln!(out, "watch acc: {}", if self.watch { "on" } else { "off" })?;
+            }
+            "l" | "list" => self.list(count(3), out)?,
+            "h" | "history" => {
+                let entries = &self.trace().entries;
+                for entry in entries.iter().skip(entries.len().saturating_sub(count(10))) {
+                    writeln!(
+                        out,
+                        "{:>5}: {:<12} acc={}",
+                        entry.iptr,
+                        entry.inst.to_string(),
+                        entry.acc
+                    )?;
+                }
+            }
+            "back" => {
+                self.back(count(1));
+                self.list(0, out)?;
+            }
+            "r" | "regs" => writeln!(out, "{:?}", self.machine.state)?,
+            "i" | "input" => {
+                let values: Result<Vec<i32>, _> = args.iter().map(|v| v.parse()).collect();
+                match values {
+                    Ok(values) => self.machine.io.input.extend(values),
+                    Err(err) => writeln!(out, "{}", err)?,
+                }
+            }
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "{}", HELP)?,
+        }
+        Ok(true)
+    }
+
+    fn trace(&self) -> &Trace {
+        self.machine.trace.as_ref().unwrap()
+    }
+
+    fn step(&mut self, out: &mut impl Write) -> io::Result<ProgramState> {
+        let iptr = self.machine.state.iptr;
+        let before = self.machine.state.acc();
+        let executed = self.machine.steps;
+        let state = self.machine.step();
+        if self.machine.steps > executed {
+            self.visits[iptr] += 1;
+        }
+        if self.watch && before != self.machine.state.acc() {
+            writeln!(out, "acc: {} -> {}", before, self.machine.state.acc())?;
+        }
+        match state {
+            ProgramState::Terminated => writeln!(out, "terminated at {}", self.machine.state.iptr)?,
+            ProgramState::Blocked => writeln!(out, "waiting for input")?,
+            ProgramState::Fault(fault) => writeln!(out, "fault: {}", fault)?,
+            ProgramState::Running => (),
+        }
+        Ok(state)
+    }
+
+    /// Undoes the last `count` instructions.
+    fn back(&mut self, count: usize) {
+        for _ in 0..count {
+            match self.machine.back() {
+                Some(entry) => self.visits[entry.iptr] -= 1,
+                None => break,
+            }
+        }
+    }
+
+    /// Breakpoint stopping the machine, `before` is the accumulator before
+    /// the last step.
+    fn hit(&self, before: i32) -> Option<&Breakpoint> {
+        let state = &self.machine.state;
+        self.breakpoints.iter().find(|bp| match bp {
+            Breakpoint::Addr(idx) => state.iptr == *idx,
+            Breakpoint::Acc(val) => state.acc() == *val && before != *val,
+            Breakpoint::Loop => self.visits.get(state.iptr).is_some_and(|v| *v > 0),
+        })
+    }
+
+    fn cont(&mut self, out: &mut impl Write) -> io::Result<()> {
+        for _ in 0..self.max_continue {
+            let before = self.machine.state.acc();
+            if self.step(out)? != ProgramState::Running {
+                return Ok(());
+            }
+            if let Some(bp) = self.hit(before) {
+                writeln!(out, "hit {:?}", bp)?;
+                return self.list(0, out);
+            }
+        }
+        writeln!(out, "no breakpoint after {} steps", self.max_continue)?;
+        self.list(0, out)
+    }
+
+    /// Disassembly of `around` instructions before and after iptr.
+    fn list(&self, around: usize, out: &mut impl Write) -> io::Result<()> {
+        let prog = self.machine.program();
+        let iptr = self.machine.state.iptr;
+        let start = iptr.saturating_sub(around);
+        let end = (iptr + around + 1).min(prog.len());
+        for (idx, inst) in prog.iter().enumerate().take(end).skip(start) {
+            let current = if idx == iptr { '>' } else { ' ' };
+            let stop = if self.breakpoints.contains(&Breakpoint::Addr(idx)) {
+                '*'
+            } else {
+                ' '
+            };
+            writeln!(out, "{}{}{:>5}: {}", current, stop, idx, inst)?;
+        }
+        if iptr >= prog.len() {
+            writeln!(out, ">  {:>5}: <end>", iptr)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_breakpoint(arg: &str) -> Option<Breakpoint> {
+    if arg == "loop" {
+        Some(Breakpoint::Loop)
+    } else if let Some(val) = arg.strip_prefix("acc=") {
+        val.parse().ok().map(Breakpoint::Acc)
+    } else {
+        arg.parse().ok().map(Breakpoint::Addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_input;
+    use super::*;
+
+    fn session(commands: &str) -> String {
+        let prog =
+            parse_input("nop +0\nacc +1\njmp +4\nacc +3\njmp -3\nacc -99\nacc +1\njmp -4\nacc +6")
+                .unwrap();
+        let mut dbg = Debugger::new(Machine::new(&prog));
+        dbg.max_continue = 1000;
+        let mut out: Vec<u8> = Vec::new();
+        dbg.repl(commands.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_break_on_loop() {
+        let out = session("break loop\nwatch\ncontinue\nregs\nhistory 2\nquit\n");
+        assert!(out.contains("acc: 2 -> 5\nhit Loop\n>     1: acc +1\n"));
+        assert!(out.contains("regs: [5, 0, 0, 0], iptr: 1"));
+        assert!(out.contains("    3: acc +3       acc=5\n    4: jmp -3       acc=5\n"));
+    }
+
+    #[test]
+    fn test_break_on_addr_and_acc() {
+        let out = session("b 4\nc\nd 1\nb acc=7\nc\n\ns 2\n");
+        assert!(out.contains("hit Addr(4)\n>*    4: jmp -3\n"));
+        assert!(out.contains("hit Acc(7)\n>     7: jmp -4\n"));
+        assert!(out.contains("no breakpoint after 1000 steps\n"));
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let prog = parse_input("acc +1\njmp -1").unwrap();
+        let mut dbg = Debugger::new(Machine::new(&prog));
+        dbg.machine.record_limit = Some(3);
+        let mut out: Vec<u8> = Vec::new();
+        dbg.repl("s 10\nback 5\nregs\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("regs: [4, 0, 0, 0], iptr: 1"));
+        assert!(dbg.trace().entries.is_empty());
+        assert_eq!(7, dbg.machine.steps);
+    }
+
+    #[test]
+    fn test_fault() {
+        let prog = parse_input("acc +1\njmp -2").unwrap();
+        let mut dbg = Debugger::new(Machine::new(&prog));
+        let mut out: Vec<u8> = Vec::new();
+        dbg.repl("s 5\nback\nregs\n".as_bytes(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("fault: jump from 1 to -1\n>     1: jmp -2\n(dbg) "));
+        assert!(out.contains("iptr: 0, halted: false, fault: None"));
+    }
+
+    #[test]
+    fn test_back() {
+        let out = session("s 4\nback 2\nregs\nhistory\n");
+        assert!(out.contains("(dbg) >     7: jmp -4\n(dbg) >     2: jmp +4\n"));
+        assert!(out.contains("regs: [1, 0, 0, 0], iptr: 2"));
+        assert!(
+            out.contains("(dbg)     0: nop +0       acc=0\n    1: acc +1       acc=1\n(dbg) \n")
+        );
+    }
+
+    #[test]
+    fn test_back_restores_registers_and_io() {
+        let prog = parse_input("set a +3\nadd a -1\nin b\nout a\nhlt").unwrap();
+        let mut dbg = Debugger::new(Machine::new(&prog));
+        let mut out: Vec<u8> = Vec::new();
+        dbg.repl("input 7\ns 4\nregs\nback 3\nregs\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("regs: [0, 2, 7, 0], iptr: 4"));
+        assert!(out.contains("regs: [0, 3, 0, 0], iptr: 1"));
+        assert_eq!(vec![7], Vec::from(dbg.machine.io.input.clone()));
+        assert!(dbg.machine.io.output.is_empty());
+        assert_eq!(1, dbg.machine.steps);
+        assert_eq!(1, dbg.trace().entries.len());
+        assert_eq!(vec![1, 0, 0, 0, 0], dbg.visits);
+    }
+}
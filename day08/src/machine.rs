@@ -0,0 +1,578 @@
+//! Handheld console. Each instruction is described once in
+//! `INSTRUCTION_SET`, which drives parsing, execution and the control-flow
+//! graph.
+
+use super::trace::{Trace, TraceEntry};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Register names, `acc` is the accumulator of the original boot code.
+pub const REGISTERS: [&str; 4] = ["acc", "a", "b", "c"];
+pub const ACC: usize = 0;
+pub const MAX_OPERANDS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Noop,
+    Acc,
+    Jump,
+    Set,
+    Add,
+    Mul,
+    JumpIfZero,
+    JumpIfNotZero,
+    Halt,
+    In,
+    Out,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    Reg,
+    Imm,
+    /// register or immediate
+    Value,
+    /// immediate jump offset, relative to the instruction
+    Offset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Reg(usize),
+    Imm(i32),
+}
+
+/// What the instruction pointer does after an instruction.
+pub enum Next {
+    Step,
+    Jump(i32),
+    Halt,
+    /// waiting for input, the instruction will run again
+    Block,
+    Overflow,
+}
+
+type Exec = fn(&mut MachineState, &mut Io, &[Operand]) -> Next;
+
+pub struct OpDef {
+    pub op: Operation,
+    pub mnemonic: &'static str,
+    /// jump targets are the `Offset` operands
+    pub operands: &'static [OperandKind],
+    /// whether the next instruction may run after this one
+    pub falls_through: bool,
+    /// operation swapped in to repair a looping program
+    pub flip: Option<Operation>,
+    exec: Exec,
+}
+
+impl OpDef {
+    /// Stops the program: neither continues nor jumps.
+    pub fn halts(&self) -> bool {
+        !self.falls_through && !self.operands.contains(&OperandKind::Offset)
+    }
+}
+
+pub const INSTRUCTION_SET: &[OpDef] = &[
+    OpDef {
+        op: Operation::Noop,
+        mnemonic: "nop",
+        operands: &[OperandKind::Imm],
+        falls_through: true,
+        flip: Some(Operation::Jump),
+        exec: |_, _, _| Next::Step,
+    },
+    OpDef {
+        op: Operation::Acc,
+        mnemonic: "acc",
+        operands: &[OperandKind::Imm],
+        falls_through: true,
+        flip: None,
+        exec: |state, _, args| {
+            let result = state.regs[ACC].checked_add(state.value(args[0]));
+            state.store(Operand::Reg(ACC), result)
+        },
+    },
+    OpDef {
+        op: Operation::Jump,
+        mnemonic: "jmp",
+        operands: &[OperandKind::Offset],
+        falls_through: false,
+        flip: Some(Operation::Noop),
+        exec: |state, _, args| Next::Jump(state.value(args[0])),
+    },
+    OpDef {
+        op: Operation::Set,
+        mnemonic: "set",
+        operands: &[OperandKind::Reg, OperandKind::Value],
+        falls_through: true,
+        flip: None,
+        exec: |state, _, args| {
+            *state.reg_mut(args[0]) = state.value(args[1]);
+            Next::Step
+        },
+    },
+    OpDef {
+        op: Operation::Add,
+        mnemonic: "add",
+        operands: &[OperandKind::Reg, OperandKind::Value],
+        falls_through: true,
+        flip: None,
+        exec: |state, _, args| {
+            let result = state.value(args[0]).checked_add(state.value(args[1]));
+            state.store(args[0], result)
+        },
+    },
+    OpDef {
+        op: Operation::Mul,
+        mnemonic: "mul",
+        operands: &[OperandKind::Reg, OperandKind::Value],
+        falls_through: true,
+        flip: None,
+        exec: |state, _, args| {
+            let result = state.value(args[0]).checked_mul(state.value(args[1]));
+            state.store(args[0], result)
+        },
+    },
+    OpDef {
+        op: Operation::JumpIfZero,
+        mnemonic: "jz",
+        operands: &[OperandKind::Value, OperandKind::Offset],
+        falls_through: true,
+        flip: None,
+        exec: |state, _, args| match state.value(args[0]) {
+            0 => Next::Jump(state.value(args[1])),
+            _ => Next::Step,
+        },
+    },
+    OpDef {
+        op: Operation::JumpIfNotZero,
+        mnemonic: "jnz",
+        operands: &[OperandKind::Value, OperandKind::Offset],
+        falls_through: true,
+        flip: None,
+        exec: |state, _, args| match state.value(args[0]) {
+            0 => Next::Step,
+            _ => Next::Jump(state.value(args[1])),
+        },
+    },
+    OpDef {
+        op: Operation::Halt,
+        mnemonic: "hlt",
+        operands: &[],
+        falls_through: false,
+        flip: None,
+        exec: |_, _, _| Next::Halt,
+    },
+    OpDef {
+        op: Operation::In,
+        mnemonic: "in",
+        operands: &[OperandKind::Reg],
+        falls_through: true,
+        flip: None,
+        exec: |state, io, args| match io.input.pop_front() {
+            Some(val) => {
+                *state.reg_mut(args[0]) = val;
+                Next::Step
+            }
+            None => Next::Block,
+        },
+    },
+    OpDef {
+        op: Operation::Out,
+        mnemonic: "out",
+        operands: &[OperandKind::Value],
+        falls_through: true,
+        flip: None,
+        exec: |state, io, args| {
+            io.output.push(state.value(args[0]));
+            Next::Step
+        },
+    },
+];
+
+impl Operation {
+    pub fn def(self) -> &'static OpDef {
+        INSTRUCTION_SET.iter().find(|def| def.op == self).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+    pub op: Operation,
+    pub args: [Operand; MAX_OPERANDS],
+}
+
+impl Instruction {
+    /// Operands actually used by the operation.
+    pub fn operands(&self) -> &[Operand] {
+        &self.args[..self.op.def().operands.len()]
+    }
+}
+
+fn parse_operand(s: &str, kind: OperandKind) -> Result<Operand, Box<dyn Error>> {
+    let reg = REGISTERS.iter().position(|r| *r == s).map(Operand::Reg);
+    match (kind, reg) {
+        (OperandKind::Reg, Some(reg)) | (OperandKind::Value, Some(reg)) => Ok(reg),
+        (OperandKind::Reg, None) => Err(format!("Unknown register {}", s).into()),
+        _ => Ok(Operand::Imm(s.parse()?)),
+    }
+}
+
+impl FromStr for Instruction {
+    type Err = Box<dyn Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+        let mnemonic = tokens.next().ok_or("Missing operation")?;
+        let def = INSTRUCTION_SET
+            .iter()
+            .find(|def| def.mnemonic == mnemonic)
+            .ok_or_else(|| format!("Unknown operation {}", mnemonic))?;
+        let tokens: Vec<&str> = tokens.collect();
+        if tokens.len() != def.operands.len() {
+            return Err(format!(
+                "{} takes {} operands, found {}",
+                mnemonic,
+                def.operands.len(),
+                tokens.len()
+            )
+            .into());
+        }
+        let mut args = [Operand::Imm(0); MAX_OPERANDS];
+        for (idx, (token, kind)) in tokens.iter().zip(def.operands.iter()).enumerate() {
+            args[idx] = parse_operand(token, *kind)?;
+        }
+        Ok(Instruction { op: def.op, args })
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => f.write_str(REGISTERS[*reg]),
+            Operand::Imm(val) => write!(f, "{:+}", val),
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.op.def().mnemonic)?;
+        for arg in self.operands() {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+pub type Program = Vec<Instruction>;
+
+#[derive(Debug, Default)]
+pub struct Io {
+    pub input: VecDeque<i32>,
+    pub output: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MachineState {
+    pub regs: [i32; REGISTERS.len()],
+    pub iptr: usize,
+    pub halted: bool,
+    pub fault: Option<Fault>,
+}
+
+impl MachineState {
+    pub fn acc(&self) -> i32 {
+        self.regs[ACC]
+    }
+
+    fn value(&self, operand: Operand) -> i32 {
+        match operand {
+            Operand::Reg(reg) => self.regs[reg],
+            Operand::Imm(val) => val,
+        }
+    }
+
+    fn reg_mut(&mut self, operand: Operand) -> &mut i32 {
+        match operand {
+            Operand::Reg(reg) => &mut self.regs[reg],
+            Operand::Imm(_) => panic!("Operand must be a register"),
+        }
+    }
+
+    /// Stores an arithmetic `result` in `reg`, `None` being an overflow.
+    fn store(&mut self, reg: Operand, result: Option<i32>) -> Next {
+        match result {
+            Some(val) => {
+                *self.reg_mut(reg) = val;
+                Next::Step
+            }
+            None => Next::Overflow,
+        }
+    }
+}
+
+/// Runtime error stopping the machine, iptr stays on the faulting instruction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// jump to a negative address or past the end of the program
+    Jump {
+        from: usize,
+        target: i64,
+    },
+    Overflow {
+        iptr: usize,
+    },
+    StepLimit(usize),
+    /// iptr set past the end of the program
+    OutOfBounds(usize),
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::Jump { from, target } => write!(f, "jump from {} to {}", from, target),
+            Fault::Overflow { iptr } => write!(f, "overflow at {}", iptr),
+            Fault::StepLimit(limit) => write!(f, "no termination after {} steps", limit),
+            Fault::OutOfBounds(iptr) => write!(f, "iptr {} out of the program", iptr),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProgramState {
+    Running,
+    /// halted or reached exactly the end of the program
+    Terminated,
+    /// waiting for input
+    Blocked,
+    Fault(Fault),
+}
+
+/// What a recorded step changed, to run it backwards.
+#[derive(Debug, Clone, Copy)]
+struct Undo {
+    /// state before the step
+    state: MachineState,
+    /// input value consumed by the step
+    input: Option<i32>,
+    /// whether the step produced an output value
+    output: bool,
+}
+
+pub struct Machine<'a> {
+    prog: &'a Program,
+    pub state: MachineState,
+    pub io: Io,
+    /// executed instructions, once `record` was called
+    pub trace: Option<Trace>,
+    /// how to undo each trace entry
+    undo: VecDeque<Undo>,
+    /// steps kept in `trace`, older ones are dropped
+    pub record_limit: Option<usize>,
+    /// steps run before faulting with `Fault::StepLimit`
+    pub step_limit: Option<usize>,
+    pub steps: usize,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(prog: &Program) -> Machine<'_> {
+        Self::with_state(prog, MachineState::default())
+    }
+
+    pub fn with_state(prog: &Program, state: MachineState) -> Machine<'_> {
+        Machine {
+            prog,
+            state,
+            io: Io::default(),
+            trace: None,
+            undo: VecDeque::new(),
+            record_limit: None,
+            step_limit: None,
+            steps: 0,
+        }
+    }
+
+    /// Starts recording executed instructions into `trace`.
+    pub fn record(&mut self) {
+        self.trace.get_or_insert_with(Trace::default);
+    }
+
+    pub fn step(&mut self) -> ProgramState {
+        if self.program_state() != ProgramState::Running {
+            return self.program_state();
+        }
+        if let Some(limit) = self.step_limit.filter(|limit| self.steps >= *limit) {
+            self.state.fault = Some(Fault::StepLimit(limit));
+            return self.program_state();
+        }
+        let iptr = self.state.iptr;
+        let inst = self.prog[iptr];
+        let mut state = self.state;
+        let (inputs, outputs) = (self.io.input.len(), self.io.output.len());
+        match (inst.op.def().exec)(&mut state, &mut self.io, inst.operands()) {
+            Next::Step => state.iptr += 1,
+            Next::Jump(off) => {
+                let target = iptr as i64 + off as i64;
+                if target < 0 || target > self.prog.len() as i64 {
+                    state.fault = Some(Fault::Jump { from: iptr, target });
+                } else {
+                    state.iptr = target as usize;
+                }
+            }
+            Next::Halt => state.halted = true,
+            Next::Block => return ProgramState::Blocked,
+            Next::Overflow => state.fault = Some(Fault::Overflow { iptr }),
+        }
+        if state.fault.is_some() {
+            // leave registers as they were before the faulting instruction
+            self.state.fault = state.fault;
+            return self.program_state();
+        }
+        let before = std::mem::replace(&mut self.state, state);
+        self.steps += 1;
+        // `in` is the only instruction consuming input, it stores it in its register
+        let input = (self.io.input.len() < inputs).then(|| state.value(inst.operands()[0]));
+        if let Some(trace) = self.trace.as_mut() {
+            trace.entries.push_back(TraceEntry {
+                iptr,
+                inst,
+                acc: self.state.acc(),
+            });
+            self.undo.push_back(Undo {
+                state: before,
+                input,
+                output: self.io.output.len() > outputs,
+            });
+            if self
+                .record_limit
+                .is_some_and(|limit| trace.entries.len() > limit)
+            {
+                trace.entries.pop_front();
+                self.undo.pop_front();
+            }
+        }
+        self.program_state()
+    }
+
+    /// Undoes the last recorded step: registers, iptr, input and output are
+    /// restored as they were before it. Returns its trace entry.
+    pub fn back(&mut self) -> Option<TraceEntry> {
+        let undo = self.undo.pop_back()?;
+        let entry = self.trace.as_mut()?.entries.pop_back();
+        self.state = undo.state;
+        if let Some(val) = undo.input {
+            self.io.input.push_front(val);
+        }
+        if undo.output {
+            self.io.output.pop();
+        }
+        self.steps -= 1;
+        entry
+    }
+
+    /// Steps until the program terminates or waits for input.
+    pub fn run(&mut self) -> ProgramState {
+        loop {
+            match self.step() {
+                ProgramState::Running => (),
+                state => return state,
+            }
+        }
+    }
+
+    pub fn program_state(&self) -> ProgramState {
+        let iptr = self.state.iptr;
+        match self.state.fault {
+            Some(fault) => ProgramState::Fault(fault),
+            None if self.state.halted || iptr == self.prog.len() => ProgramState::Terminated,
+            None if iptr > self.prog.len() => ProgramState::Fault(Fault::OutOfBounds(iptr)),
+            None => ProgramState::Running,
+        }
+    }
+
+    pub fn program(&self) -> &'a Program {
+        self.prog
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::asm::assemble;
+    use super::*;
+
+    #[test]
+    fn test_parse_display() {
+        let text = "nop +0\nacc -4\njmp +2\nset b a\nadd a -1\nmul acc c\njz a +3\njnz +1 -2\nhlt\nin c\nout b";
+        let prog = assemble(text).unwrap();
+        assert_eq!(11, prog.len());
+        let printed: Vec<String> = prog.iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, printed.join("\n"));
+        assert!(assemble("mul +2 +3").is_err());
+        assert!(assemble("acc +1 +2").is_err());
+        assert!(assemble("nop +0\nfoo +1").is_err());
+    }
+
+    #[test]
+    fn test_run_factorial() {
+        // a! in acc, outputting each partial product
+        let prog = assemble(
+            "in a
+set acc +1
+jz a +5
+mul acc a
+out acc
+add a -1
+jmp -4
+hlt
+acc +100",
+        )
+        .unwrap();
+        let mut m = Machine::new(&prog);
+        assert_eq!(ProgramState::Blocked, m.run());
+        m.io.input.push_back(5);
+        assert_eq!(ProgramState::Terminated, m.run());
+        assert_eq!(120, m.state.acc());
+        assert_eq!(vec![5, 20, 60, 120, 120], m.io.output);
+        assert_eq!(7, m.state.iptr);
+    }
+
+    #[test]
+    fn test_faults() {
+        let run = |text: &str| {
+            let prog = assemble(text).unwrap();
+            let mut m = Machine::new(&prog);
+            m.step_limit = Some(100);
+            (m.run(), m.state)
+        };
+        let (state, regs) = run("acc +1\njmp -2");
+        assert_eq!(
+            ProgramState::Fault(Fault::Jump {
+                from: 1,
+                target: -1
+            }),
+            state
+        );
+        assert_eq!((1, 1), (regs.acc(), regs.iptr));
+        assert_eq!(
+            ProgramState::Fault(Fault::Jump { from: 0, target: 3 }),
+            run("jmp +3\nnop +0").0
+        );
+        assert_eq!(ProgramState::Terminated, run("nop +0\njmp +1").0);
+        let (state, regs) = run("set acc +2147483647\nacc +1");
+        assert_eq!(ProgramState::Fault(Fault::Overflow { iptr: 1 }), state);
+        assert_eq!(i32::MAX, regs.acc());
+        assert_eq!(
+            ProgramState::Fault(Fault::Overflow { iptr: 2 }),
+            run("set a +65536\nset b a\nmul a b").0
+        );
+        assert_eq!(
+            ProgramState::Fault(Fault::StepLimit(100)),
+            run("acc +1\njmp -1").0
+        );
+    }
+}
@@ -1,159 +1,137 @@
-use std::{env, fs};
+mod asm;
+mod cfg;
+mod debugger;
+mod machine;
+mod trace;
+
+use cfg::{Cfg, Flip};
+use machine::{Machine, Program, ProgramState};
+use std::{env, error::Error, fs, io};
+use trace::Trace;
 
 fn get_input_path() -> String {
     let args: Vec<String> = env::args().collect();
     args.get(1).unwrap().clone()
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Operation {
-    Noop,
-    Acc,
-    Jump,
-}
-
-#[derive(Debug, Clone, Copy)]
-struct Instruction {
-    pub op: Operation,
-    pub arg: i32,
-}
+/// Steps before `--run` or part 2 give up on a program.
+const RUN_STEP_LIMIT: usize = 100_000_000;
 
-type Program = Vec<Instruction>;
 type Input = Program;
 type Output1 = i32;
 type Output2 = i32;
 
-fn parse_input(input: &str) -> Input {
-    input
-        .lines()
-        .map(|l| {
-            let tokens: Vec<&str> = l.split(' ').collect();
-            let inst = tokens.get(0).unwrap();
-            let arg = tokens.get(1).unwrap().parse::<i32>().unwrap();
-            let op = match *inst {
-                "nop" => Operation::Noop,
-                "acc" => Operation::Acc,
-                "jmp" => Operation::Jump,
-                _ => panic!("Unknown operation"),
-            };
-            Instruction { op, arg }
-        })
-        .collect()
-}
-
-struct Machine<'a> {
-    prog: &'a Program,
-    pub state: MachineState,
-}
-
-#[derive(Clone, Copy)]
-struct MachineState {
-    pub acc: i32,
-    pub iptr: usize,
-}
-
-#[derive(PartialEq)]
-enum ProgramState {
-    Running,
-    Terminated,
-}
-
-impl<'a> Machine<'a> {
-    pub fn new(prog: &Program) -> Machine {
-        let state = MachineState { iptr: 0, acc: 0 };
-        Self::with_state(prog, state)
-    }
-
-    pub fn with_state(prog: &Program, state: MachineState) -> Machine {
-        Machine { prog, state }
-    }
-
-    pub fn step(&mut self) -> ProgramState {
-        let inst = match self.prog.get(self.state.iptr) {
-            Some(i) => i,
-            None => return ProgramState::Terminated,
-        };
-        match inst.op {
-            Operation::Noop => self.state.iptr += 1,
-            Operation::Jump => self.state.iptr = (self.state.iptr as i32 + inst.arg) as usize,
-            Operation::Acc => {
-                self.state.acc += inst.arg;
-                self.state.iptr += 1;
-            }
-        }
-        self.program_state()
-    }
-
-    pub fn program_state(&self) -> ProgramState {
-        match self.prog.get(self.state.iptr) {
-            Some(_) => ProgramState::Running,
-            None => ProgramState::Terminated,
-        }
-    }
-
-    pub fn next_op(&self) -> Operation {
-        self.prog.get(self.state.iptr).unwrap().op
-    }
+fn parse_input(input: &str) -> Result<Input, Box<dyn Error>> {
+    asm::assemble(input)
 }
 
 fn solve_part_1(input: &Input) -> Output1 {
-    let mut executed: Vec<bool> = Vec::with_capacity(input.len());
-    for _ in 0..input.len() {
-        executed.push(false);
-    }
+    let mut executed: Vec<bool> = vec![false; input.len()];
     let mut m: Machine = Machine::new(input);
-    while !executed[m.state.iptr] {
+    while m.program_state() == ProgramState::Running && !executed[m.state.iptr] {
         executed[m.state.iptr] = true;
         m.step();
     }
-    m.state.acc
+    m.state.acc()
 }
 
+/// Runs until the machine stops or is about to loop, returns whether it loops.
 fn program_loops(m: &mut Machine, exec_cache: &mut [bool]) -> bool {
-    while !exec_cache[m.state.iptr] {
+    while m.program_state() == ProgramState::Running && !exec_cache[m.state.iptr] {
         exec_cache[m.state.iptr] = true;
-        if let ProgramState::Terminated = m.step() {
+        if m.step() != ProgramState::Running {
             return false;
         }
     }
-    true
+    m.program_state() == ProgramState::Running
 }
 
-fn solve_part_2(input: &Input) -> Output2 {
-    let mut executed: Vec<bool> = Vec::with_capacity(input.len());
-    for _ in 0..input.len() {
-        executed.push(false);
-    }
-    let mut mach = Machine::new(&input);
+/// First jmp or nop which lets the program terminate once flipped.
+fn find_patch(input: &Input) -> Option<Flip> {
+    Cfg::new(input).candidates(input).first().copied()
+}
+
+fn patched(input: &Input, flip: Flip) -> Program {
     let mut modded = input.clone();
-    loop {
-        // step until next branching
-        while mach.next_op() != Operation::Jump && mach.next_op() != Operation::Noop {
-            mach.step();
-        }
-        // patch code before continuing
-        let op: &mut Operation = &mut modded[mach.state.iptr].op;
-        match op {
-            Operation::Jump => *op = Operation::Noop,
-            Operation::Noop => *op = Operation::Jump,
-            _ => panic!("Unexpected instruction to patch"),
-        }
-        // create alternative machine with patched code
-        let mut alternative = Machine::with_state(&modded, mach.state);
-        if program_loops(&mut alternative, &mut executed) {
-            executed.iter_mut().for_each(|e| *e = false);
-            // step real machine on original flow control instruction
-            mach.step();
-        } else {
-            return alternative.state.acc;
-        }
-    }
+    modded[flip.idx] = flip.inst;
+    modded
+}
+
+fn solve_part_2(input: &Input) -> Output2 {
+    let modded = patched(input, find_patch(input).unwrap());
+    let mut mach = Machine::new(&modded);
+    // with conditional jumps the patched program only may terminate
+    mach.step_limit = Some(RUN_STEP_LIMIT);
+    assert_eq!(ProgramState::Terminated, mach.run());
+    mach.state.acc()
+}
+
+/// Trace of `prog` until it terminates or is about to loop.
+fn record_until_loop(prog: &Program) -> Trace {
+    let mut m = Machine::new(prog);
+    m.record();
+    program_loops(&mut m, &mut vec![false; prog.len()]);
+    m.trace.unwrap()
+}
+
+fn print_diff(a: &Trace, b: &Trace) {
+    trace::diff(a, b).iter().for_each(|l| println!("{}", l));
 }
 
 fn main() {
     let input_path = get_input_path();
     let raw_input = fs::read_to_string(input_path).unwrap();
-    let input = parse_input(&raw_input);
+    let input = parse_input(&raw_input).unwrap();
+    let args: Vec<String> = env::args().collect();
+    if args.iter().any(|a| a == "--debug") {
+        let mut dbg = debugger::Debugger::new(Machine::new(&input));
+        dbg.repl(io::stdin().lock(), &mut io::stdout()).unwrap();
+        return;
+    }
+    // dump the trace of the program until it loops
+    if let Some(pos) = args.iter().position(|a| a == "--trace") {
+        let mut file = fs::File::create(&args[pos + 1]).unwrap();
+        record_until_loop(&input).dump(&mut file).unwrap();
+        return;
+    }
+    // compare two traces dumped with --trace
+    if let Some(pos) = args.iter().position(|a| a == "--diff-traces") {
+        let load = |path: &String| -> Trace { fs::read_to_string(path).unwrap().parse().unwrap() };
+        print_diff(&load(&args[pos + 1]), &load(&args[pos + 2]));
+        return;
+    }
+    // show what the part 2 patch changes in the execution
+    if args.iter().any(|a| a == "--explain") {
+        let flip = find_patch(&input).expect("No patch lets the program terminate");
+        println!("Flipping instruction {}: {}", flip.idx, input[flip.idx]);
+        print_diff(
+            &record_until_loop(&input),
+            &record_until_loop(&patched(&input, flip)),
+        );
+        return;
+    }
+    // print the program with labels instead of jump offsets
+    if args.iter().any(|a| a == "--disasm") {
+        print!("{}", asm::disassemble(&input));
+        return;
+    }
+    // list every flip which lets the program terminate
+    if args.iter().any(|a| a == "--candidates") {
+        for flip in Cfg::new(&input).candidates(&input) {
+            println!("{}: {} -> {}", flip.idx, input[flip.idx], flip.inst);
+        }
+        return;
+    }
+    // run the program as is, feeding it the numbers following --run
+    if let Some(pos) = args.iter().position(|a| a == "--run") {
+        let mut m = Machine::new(&input);
+        m.step_limit = Some(RUN_STEP_LIMIT);
+        m.io.input = args[pos + 1..].iter().map(|a| a.parse().unwrap()).collect();
+        let state = m.run();
+        println!("{:?} at {} with {:?}", state, m.state.iptr, m.state.regs);
+        println!("Output: {:?}", m.io.output);
+        return;
+    }
     let part_1_result = solve_part_1(&input);
     println!("Part 1: {:?}", part_1_result);
     let part_2_result = solve_part_2(&input);
@@ -175,15 +153,26 @@ acc +6";
 
     #[test]
     fn test_part1_ex() {
-        let input = parse_input(EX_INPUT);
+        let input = parse_input(EX_INPUT).unwrap();
         let res = solve_part_1(&input);
         assert_eq!(5, res);
     }
 
     #[test]
     fn test_part2_ex() {
-        let input = parse_input(EX_INPUT);
+        let input = parse_input(EX_INPUT).unwrap();
         let res = solve_part_2(&input);
         assert_eq!(8, res);
     }
+
+    #[test]
+    fn test_record_until_loop() {
+        let input = parse_input(EX_INPUT).unwrap();
+        let trace = record_until_loop(&input);
+        let iptrs: Vec<usize> = trace.entries.iter().map(|e| e.iptr).collect();
+        assert_eq!(vec![0, 1, 2, 6, 7, 3, 4], iptrs);
+        assert!(record_until_loop(&parse_input("").unwrap())
+            .entries
+            .is_empty());
+    }
 }
@@ -0,0 +1,159 @@
+//! Execution traces recorded by a `Machine`, they can be dumped to and loaded
+//! from text and diffed. `Machine::back` replays them backwards.
+
+use super::machine::Instruction;
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::{self, Display},
+    io::{self, Write},
+    str::FromStr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TraceEntry {
+    pub iptr: usize,
+    pub inst: Instruction,
+    /// accumulator after the instruction
+    pub acc: i32,
+}
+
+/// Dump format, one tab separated entry per line.
+impl Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", self.iptr, self.inst, self.acc)
+    }
+}
+
+impl FromStr for TraceEntry {
+    type Err = Box<dyn Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split('\t').collect();
+        if fields.len() != 3 {
+            return Err(Box::<dyn Error>::from("Trace entry must have 3 fields"));
+        }
+        Ok(TraceEntry {
+            iptr: fields[0].parse()?,
+            inst: fields[1].parse()?,
+            acc: fields[2].parse()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    pub entries: VecDeque<TraceEntry>,
+}
+
+impl Trace {
+    pub fn dump(&self, out: &mut impl Write) -> io::Result<()> {
+        for entry in self.entries.iter() {
+            writeln!(out, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Trace {
+    type Err = Box<dyn Error>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let entries = s
+            .lines()
+            .enumerate()
+            .map(|(idx, l)| {
+                l.parse()
+                    .map_err(|err| format!("line {}: {}", idx + 1, err).into())
+            })
+            .collect::<Result<VecDeque<TraceEntry>, Box<dyn Error>>>()?;
+        Ok(Trace { entries })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiffLine<'t> {
+    /// run of identical entries
+    Same(usize),
+    Removed(&'t TraceEntry),
+    Added(&'t TraceEntry),
+}
+
+impl<'t> Display for DiffLine<'t> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffLine::Same(count) => write!(f, "  ({} identical steps)", count),
+            DiffLine::Removed(entry) => write!(f, "- {}", entry),
+            DiffLine::Added(entry) => write!(f, "+ {}", entry),
+        }
+    }
+}
+
+/// Differences between two traces: their common start and end are collapsed,
+/// everything in between is shown as removed from `a` and added in `b`.
+pub fn diff<'t>(a: &'t Trace, b: &'t Trace) -> Vec<DiffLine<'t>> {
+    let (a, b) = (&a.entries, &b.entries);
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a
+        .range(prefix..)
+        .rev()
+        .zip(b.range(prefix..).rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let mut lines: Vec<DiffLine> = Vec::new();
+    if prefix > 0 {
+        lines.push(DiffLine::Same(prefix));
+    }
+    lines.extend(a.range(prefix..a.len() - suffix).map(DiffLine::Removed));
+    lines.extend(b.range(prefix..b.len() - suffix).map(DiffLine::Added));
+    if suffix > 0 {
+        lines.push(DiffLine::Same(suffix));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::machine::Machine;
+    use super::super::parse_input;
+    use super::*;
+
+    fn trace_of(prog: &str, steps: usize) -> Trace {
+        let prog = parse_input(prog).unwrap();
+        let mut m = Machine::new(&prog);
+        m.record();
+        for _ in 0..steps {
+            m.step();
+        }
+        m.trace.unwrap()
+    }
+
+    #[test]
+    fn test_dump_load() {
+        let trace = trace_of("acc +2\njmp +2\nacc +5\nacc -1", 4);
+        assert_eq!(3, trace.entries.len());
+        let mut dump: Vec<u8> = Vec::new();
+        trace.dump(&mut dump).unwrap();
+        let dump = String::from_utf8(dump).unwrap();
+        assert_eq!("0\tacc +2\t2\n1\tjmp +2\t2\n3\tacc -1\t1\n", dump);
+        assert_eq!(trace, dump.parse().unwrap());
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = trace_of("acc +1\njmp +2\nacc +5\nnop +0\nacc +1", 5);
+        let b = trace_of("acc +1\nnop +2\nacc +5\nnop +0\nacc +1", 5);
+        let diff: Vec<String> = diff(&a, &b).iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            vec![
+                "  (1 identical steps)",
+                "- 1\tjmp +2\t1",
+                "- 3\tnop +0\t1",
+                "- 4\tacc +1\t2",
+                "+ 1\tnop +2\t1",
+                "+ 2\tacc +5\t6",
+                "+ 3\tnop +0\t6",
+                "+ 4\tacc +1\t7",
+            ],
+            diff
+        );
+    }
+}
@@ -1,6 +1,10 @@
 use std::{
-    collections::{HashSet, VecDeque},
-    env, fs,
+    collections::{HashMap, VecDeque},
+    env,
+    error::Error,
+    fs,
+    io::{self, BufRead, BufReader},
+    ops::RangeInclusive,
 };
 
 fn get_input_path() -> String {
@@ -8,84 +12,205 @@ fn get_input_path() -> String {
     args.get(1).unwrap().clone()
 }
 
+/// Preamble length given with `--preamble=<n>`, 25 by default.
+fn get_preamble_len() -> Result<usize, Box<dyn Error>> {
+    let preamble_len =
+        match env::args().find_map(|a| a.strip_prefix("--preamble=").map(String::from)) {
+            Some(n) => n.parse()?,
+            None => 25,
+        };
+    check_preamble_len(preamble_len)?;
+    Ok(preamble_len)
+}
+
+/// Smallest preamble holding a pair of numbers.
+const MIN_PREAMBLE_LEN: usize = 2;
+
+fn check_preamble_len(preamble_len: usize) -> Result<(), Box<dyn Error>> {
+    if preamble_len < MIN_PREAMBLE_LEN {
+        return Err(format!("Preamble must hold at least {} numbers", MIN_PREAMBLE_LEN).into());
+    }
+    Ok(())
+}
+
 type Number = u64;
 type Input = Vec<Number>;
 type Output1 = Number;
 type Output2 = Number;
 
-fn parse_input(input: &str) -> Input {
+fn parse_input(input: &str) -> Result<Input, Box<dyn Error>> {
     input
         .lines()
-        .map(|l| l.parse::<Number>().unwrap())
+        .map(|l| l.parse::<Number>().map_err(|err| err.into()))
         .collect()
 }
 
-fn xmas_find_weakness_target(input: &Input, preamble_len: usize) -> Output1 {
-    let mut cache: VecDeque<HashSet<Number>> = VecDeque::with_capacity(preamble_len);
-    for a in input.iter().take(preamble_len) {
-        let mut sums: HashSet<Number> = HashSet::with_capacity(preamble_len - 1);
-        for b in input.iter().take(preamble_len) {
-            if a != b {
-                sums.insert(a + b);
-            }
-        }
-        cache.push_back(sums);
+/// Checks numbers one at a time against the sums of pairs of the
+/// `preamble_len` numbers before them. Pairs are made of distinct positions,
+/// their values may be equal.
+struct Validator {
+    preamble_len: usize,
+    window: VecDeque<Number>,
+    /// how many pairs of the window give each sum
+    sums: HashMap<Number, usize>,
+}
+
+impl Validator {
+    fn new(preamble_len: usize) -> Result<Validator, Box<dyn Error>> {
+        check_preamble_len(preamble_len)?;
+        Ok(Validator {
+            preamble_len,
+            window: VecDeque::with_capacity(preamble_len),
+            sums: HashMap::new(),
+        })
     }
-    for window in input.windows(preamble_len + 1) {
-        let (to_check, leading) = window.split_last().unwrap();
-        if cache.iter().find(|sums| sums.contains(to_check)).is_none() {
-            return *to_check;
-        }
-        // prepare next iteration by:
-        //  - removing sums of first value (out of scope at next iteration)
-        //  - adding sums of incoming check value (in scope at next iteration)
-        cache.pop_front();
-        let (_, kept) = leading.split_first().unwrap();
-        let mut next_sums: HashSet<Number> = HashSet::with_capacity(preamble_len - 1);
-        for n in kept {
-            if n != to_check {
-                next_sums.insert(n + to_check);
+
+    /// Sums of `n` with every number of the window, a sum too large can't
+    /// match any number.
+    fn sums_with(&self, n: Number) -> impl Iterator<Item = Number> + '_ {
+        self.window.iter().filter_map(move |k| k.checked_add(n))
+    }
+
+    /// Checks `n` then slides the window over it, numbers of the preamble are
+    /// always valid.
+    fn push(&mut self, n: Number) -> bool {
+        let valid = self.window.len() < self.preamble_len || self.sums.contains_key(&n);
+        if self.window.len() == self.preamble_len {
+            // first value is out of scope for the next number
+            let first = self.window.pop_front().unwrap();
+            for sum in self.sums_with(first).collect::<Vec<Number>>() {
+                let count = self.sums.get_mut(&sum).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.sums.remove(&sum);
+                }
             }
         }
-        cache.push_back(next_sums);
+        for sum in self.sums_with(n).collect::<Vec<Number>>() {
+            *self.sums.entry(sum).or_insert(0) += 1;
+        }
+        self.window.push_back(n);
+        valid
     }
-    panic!("Failed to find weakness target");
 }
 
-fn solve_part_1(input: &Input) -> Output1 {
-    xmas_find_weakness_target(input, 25)
+#[derive(Debug, PartialEq)]
+struct Invalid {
+    /// 0 based position in the feed
+    index: usize,
+    value: Number,
+}
+
+/// Every invalid number of a feed with one number per line, read as it comes.
+fn invalid_numbers(
+    feed: impl BufRead,
+    preamble_len: usize,
+) -> Result<impl Iterator<Item = Result<Invalid, Box<dyn Error>>>, Box<dyn Error>> {
+    let mut validator = Validator::new(preamble_len)?;
+    Ok(feed.lines().enumerate().filter_map(move |(index, line)| {
+        let value = line
+            .map_err(|err| err.into())
+            .and_then(|l| l.trim().parse::<Number>().map_err(|err| err.into()))
+            .map_err(|err: Box<dyn Error>| format!("line {}: {}", index + 1, err));
+        match value {
+            Ok(value) if validator.push(value) => None,
+            Ok(value) => Some(Ok(Invalid { index, value })),
+            Err(err) => Some(Err(err.into())),
+        }
+    }))
+}
+
+fn xmas_find_weakness_target(
+    input: &[Number],
+    preamble_len: usize,
+) -> Result<Option<Output1>, Box<dyn Error>> {
+    let mut validator = Validator::new(preamble_len)?;
+    Ok(input.iter().copied().find(|n| !validator.push(*n)))
+}
+
+fn solve_part_1(input: &Input, preamble_len: usize) -> Result<Option<Output1>, Box<dyn Error>> {
+    xmas_find_weakness_target(input, preamble_len)
 }
 
-fn xmas_find_weakness(input: &Input, target: Number) -> Number {
-    let mut start: usize = 0;
-    let mut end: usize = 0;
-    let mut sum = input[0];
-    while sum != target {
-        if sum < target {
-            end += 1;
-            sum += input[end];
-        } else {
-            sum -= input[start];
-            start += 1;
+/// Every contiguous range of at least two numbers summing to `target`,
+/// ordered by start then end, `None` when there is none.
+fn xmas_weak_ranges(input: &[Number], target: Number) -> Option<Vec<RangeInclusive<usize>>> {
+    // prefix sum before each index, as wide as needed to never overflow
+    let mut prefix: u128 = 0;
+    let mut starts: HashMap<u128, Vec<usize>> = HashMap::new();
+    let mut ranges: Vec<RangeInclusive<usize>> = Vec::new();
+    for (end, n) in input.iter().enumerate() {
+        // prefix before `end - 1` becomes a valid start for ranges ending here
+        if end >= 1 {
+            let before = prefix - input[end - 1] as u128;
+            starts.entry(before).or_default().push(end - 1);
+        }
+        prefix += *n as u128;
+        if let Some(found) = prefix
+            .checked_sub(target as u128)
+            .and_then(|start_prefix| starts.get(&start_prefix))
+        {
+            ranges.extend(found.iter().map(|start| *start..=end));
         }
     }
-    let target_sum = &input[start..=end];
-    return target_sum.iter().min().unwrap() + target_sum.iter().max().unwrap();
+    ranges.sort_by_key(|r| (*r.start(), *r.end()));
+    Some(ranges).filter(|r| !r.is_empty())
 }
 
-fn solve_part_2(input: &Input) -> Output2 {
-    let target = xmas_find_weakness_target(input, 25);
-    xmas_find_weakness(input, target)
+/// Sum of the smallest and largest numbers of `range`.
+fn weakness(input: &[Number], range: RangeInclusive<usize>) -> Number {
+    let numbers = &input[range];
+    numbers.iter().min().unwrap() + numbers.iter().max().unwrap()
+}
+
+fn xmas_find_weakness(input: &[Number], target: Number) -> Option<Number> {
+    let first = xmas_weak_ranges(input, target)?.remove(0);
+    Some(weakness(input, first))
+}
+
+fn solve_part_2(input: &Input, preamble_len: usize) -> Result<Option<Output2>, Box<dyn Error>> {
+    let target = xmas_find_weakness_target(input, preamble_len)?;
+    Ok(target.and_then(|target| xmas_find_weakness(input, target)))
 }
 
 fn main() {
     let input_path = get_input_path();
+    let preamble_len = get_preamble_len().unwrap();
+    // report every invalid number of the file, or of stdin for "-"
+    if env::args().any(|a| a == "--validate") {
+        let feed: Box<dyn BufRead> = match input_path.as_str() {
+            "-" => Box::new(BufReader::new(io::stdin())),
+            path => Box::new(BufReader::new(fs::File::open(path).unwrap())),
+        };
+        for invalid in invalid_numbers(feed, preamble_len).unwrap() {
+            match invalid {
+                Ok(Invalid { index, value }) => println!("{}: {}", index, value),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+        return;
+    }
     let raw_input = fs::read_to_string(input_path).unwrap();
-    let input = parse_input(&raw_input);
-    let part_1_result = solve_part_1(&input);
-    println!("Part 1: {:?}", part_1_result);
-    let part_2_result = solve_part_2(&input);
-    println!("Part 2: {:?}", part_2_result);
+    let input = parse_input(&raw_input).unwrap();
+    match solve_part_1(&input, preamble_len).unwrap() {
+        Some(res) => println!("Part 1: {:?}", res),
+        None => println!("Part 1: every number is valid"),
+    }
+    match solve_part_2(&input, preamble_len).unwrap() {
+        Some(res) => println!("Part 2: {:?}", res),
+        None => println!("Part 2: no range sums to the target"),
+    }
+    // list every range summing to the part 1 target
+    if env::args().any(|a| a == "--ranges") {
+        let target = solve_part_1(&input, preamble_len).unwrap();
+        for range in target
+            .and_then(|t| xmas_weak_ranges(&input, t))
+            .unwrap_or_default()
+        {
+            let weak = weakness(&input, range.clone());
+            println!("{}..={}: {}", range.start(), range.end(), weak);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -115,15 +240,73 @@ mod tests {
 
     #[test]
     fn text_part1_example() {
-        let input = parse_input(EX_INPUT);
-        let res = xmas_find_weakness_target(&input, 5);
-        assert_eq!(127, res);
+        let input = parse_input(EX_INPUT).unwrap();
+        let res = xmas_find_weakness_target(&input, 5).unwrap();
+        assert_eq!(Some(127), res);
+        assert_eq!(None, xmas_find_weakness_target(&input[..14], 5).unwrap());
+    }
+
+    #[test]
+    fn test_duplicate_values() {
+        // 6 is the sum of the two 3, 4 of the two 2
+        let feed = "3\n3\n6\n9\n15\n12\n2\n2\n4";
+        let invalid: Vec<Number> = invalid_numbers(feed.as_bytes(), 2)
+            .unwrap()
+            .map(|res| res.unwrap().value)
+            .collect();
+        assert_eq!(vec![12, 2, 2], invalid);
+        let input = parse_input(feed).unwrap();
+        assert_eq!(Some(12), xmas_find_weakness_target(&input, 2).unwrap());
+    }
+
+    #[test]
+    fn test_short_preamble() {
+        for preamble_len in 0..2 {
+            assert!(xmas_find_weakness_target(&[1, 2, 3], preamble_len).is_err());
+            assert!(invalid_numbers("1\n2\n3".as_bytes(), preamble_len).is_err());
+        }
+        assert_eq!(None, xmas_find_weakness_target(&[1, 2, 3], 2).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        let feed = format!("{}\n1\nfoo\n15", EX_INPUT);
+        let found: Vec<String> = invalid_numbers(feed.as_bytes(), 5)
+            .unwrap()
+            .map(|res| match res {
+                Ok(Invalid { index, value }) => format!("{}: {}", index, value),
+                Err(err) => err.to_string(),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "14: 127",
+                "20: 1",
+                "line 22: invalid digit found in string",
+                "22: 15"
+            ],
+            found
+        );
     }
 
     #[test]
     fn text_part2_example() {
-        let input = parse_input(EX_INPUT);
+        let input = parse_input(EX_INPUT).unwrap();
         let res = xmas_find_weakness(&input, 127);
-        assert_eq!(62, res);
+        assert_eq!(Some(62), res);
+        // 127 alone is not a range
+        assert_eq!(Some(vec![2..=5]), xmas_weak_ranges(&input, 127));
+        assert_eq!(None, xmas_weak_ranges(&input, 1000));
+        assert_eq!(None, xmas_weak_ranges(&input, 20));
+    }
+
+    #[test]
+    fn test_all_ranges() {
+        let input = parse_input("1\n2\n0\n3\n0\n0\n5").unwrap();
+        assert_eq!(
+            Some(vec![0..=1, 0..=2, 2..=3, 2..=4, 2..=5, 3..=4, 3..=5]),
+            xmas_weak_ranges(&input, 3)
+        );
+        assert_eq!(Some(vec![4..=5]), xmas_weak_ranges(&input, 0));
     }
 }
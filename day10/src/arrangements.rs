@@ -0,0 +1,249 @@
+//! Arrangements themselves rather than their count: lazy enumeration in
+//! lexicographic order, direct access to the k-th one and uniform sampling.
+//!
+//! An arrangement is the list of joltages from the outlet to the device.
+
+use super::{chain, path_counts, Input, Jolt, Tolerance};
+use std::error::Error;
+
+/// Chain of joltages with the arrangements leading from each to the device.
+pub struct Arrangements {
+    chain: Vec<Jolt>,
+    tolerance: Tolerance,
+    /// arrangements from each joltage to the device, saturating at u64::MAX
+    /// which is enough to compare them with any k below it
+    to_device: Vec<u64>,
+}
+
+impl Arrangements {
+    pub fn new(input: &Input, tolerance: &Tolerance) -> Arrangements {
+        let chain = chain(input, tolerance);
+        let mut to_device = vec![0; chain.len()];
+        to_device[chain.len() - 1] = 1;
+        for idx in (0..chain.len() - 1).rev() {
+            to_device[idx] = (idx + 1..chain.len())
+                .take_while(|next| chain[*next] <= chain[idx] + tolerance.max_step)
+                .filter(|next| tolerance.accepts(chain[idx], chain[*next]))
+                .fold(0u64, |sum, next| sum.saturating_add(to_device[next]));
+        }
+        Arrangements {
+            chain,
+            tolerance: *tolerance,
+            to_device,
+        }
+    }
+
+    /// Following joltages from which the device can still be reached, in
+    /// increasing order.
+    fn next(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        (idx + 1..self.chain.len())
+            .take_while(move |next| self.chain[*next] <= self.chain[idx] + self.tolerance.max_step)
+            .filter(move |next| {
+                self.tolerance.accepts(self.chain[idx], self.chain[*next])
+                    && self.to_device[*next] > 0
+            })
+    }
+
+    fn jolts(&self, path: &[usize]) -> Vec<Jolt> {
+        path.iter().map(|idx| self.chain[*idx]).collect()
+    }
+
+    /// Extends `path` with the smallest joltages up to the device.
+    fn descend(&self, path: &mut Vec<usize>) {
+        while let Some(next) = self.next(*path.last().unwrap()).next() {
+            path.push(next);
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            arrangements: self,
+            path: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// Arrangement at 0 based index `k` in lexicographic order.
+    pub fn nth(&self, mut k: u64) -> Option<Vec<Jolt>> {
+        if k >= self.to_device[0] {
+            return None;
+        }
+        let mut path = vec![0];
+        while let Some(&idx) = path.last().filter(|idx| **idx != self.chain.len() - 1) {
+            for next in self.next(idx) {
+                if k < self.to_device[next] {
+                    path.push(next);
+                    break;
+                }
+                k -= self.to_device[next];
+            }
+        }
+        Some(self.jolts(&path))
+    }
+}
+
+/// Lazy enumeration of arrangements in lexicographic order.
+pub struct Iter<'a> {
+    arrangements: &'a Arrangements,
+    /// indices of the last arrangement, empty before the first one
+    path: Vec<usize>,
+    /// set once every arrangement was returned
+    done: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Vec<Jolt>;
+
+    fn next(&mut self) -> Option<Vec<Jolt>> {
+        let arr = self.arrangements;
+        if self.done {
+            return None;
+        }
+        if self.path.is_empty() {
+            if arr.to_device[0] == 0 {
+                self.done = true;
+                return None;
+            }
+            self.path.push(0);
+        } else {
+            // replace the deepest joltage having a larger sibling
+            loop {
+                let last = self.path.pop().unwrap();
+                let parent = match self.path.last() {
+                    Some(parent) => *parent,
+                    None => {
+                        self.done = true;
+                        return None;
+                    }
+                };
+                if let Some(sibling) = arr.next(parent).find(|next| *next > last) {
+                    self.path.push(sibling);
+                    break;
+                }
+            }
+        }
+        arr.descend(&mut self.path);
+        Some(arr.jolts(&self.path))
+    }
+}
+
+/// SplitMix64 generator, good enough to pick arrangements.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..n`, rejecting draws which would bias the modulo.
+    fn below(&mut self, n: u64) -> u64 {
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let draw = self.next_u64();
+            if draw < zone {
+                return draw % n;
+            }
+        }
+    }
+}
+
+/// Uniformly random arrangement, walking back from the device and picking
+/// each previous joltage in proportion to the arrangements leading to it.
+pub fn sample(
+    input: &Input,
+    tolerance: &Tolerance,
+    rng: &mut Rng,
+) -> Result<Vec<Jolt>, Box<dyn Error>> {
+    let chain = chain(input, tolerance);
+    let counts = path_counts(&chain, tolerance, 0, 1, |a: &u64, b| a.checked_add(*b))
+        .ok_or("Too many arrangements to sample them")?;
+    let mut idx = chain.len() - 1;
+    if counts[idx] == 0 {
+        return Err("No arrangement reaches the device".into());
+    }
+    let mut picked = vec![chain[idx]];
+    while idx > 0 {
+        let mut draw = rng.below(counts[idx]);
+        idx = (0..idx)
+            .rev()
+            .take_while(|prev| chain[*prev] + tolerance.max_step >= chain[idx])
+            .filter(|prev| tolerance.accepts(chain[*prev], chain[idx]))
+            .find(|prev| {
+                if draw < counts[*prev] {
+                    return true;
+                }
+                draw -= counts[*prev];
+                false
+            })
+            .unwrap();
+        picked.push(chain[idx]);
+    }
+    picked.reverse();
+    Ok(picked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse_input;
+    use super::*;
+    use std::collections::HashMap;
+
+    const INPUT: &str = "16\n10\n15\n5\n1\n11\n7\n19\n6\n12\n4";
+
+    #[test]
+    fn test_enumerate_nth() {
+        let input = parse_input(INPUT);
+        let arr = Arrangements::new(&input, &Tolerance::CLASSIC);
+        let all: Vec<Vec<Jolt>> = arr.iter().collect();
+        assert_eq!(8, all.len());
+        assert_eq!(vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 22], all[0]);
+        assert_eq!(vec![0, 1, 4, 7, 10, 12, 15, 16, 19, 22], all[7]);
+        let mut sorted = all.clone();
+        sorted.sort();
+        assert_eq!(sorted, all);
+        for (k, arrangement) in all.iter().enumerate() {
+            assert_eq!(Some(arrangement), arr.nth(k as u64).as_ref());
+        }
+        assert_eq!(None, arr.nth(8));
+        // lazily enumerated even when there are too many to count
+        let input: Input = (1..=300).collect();
+        let arr = Arrangements::new(&input, &Tolerance::CLASSIC);
+        let first: Vec<Vec<Jolt>> = arr.iter().take(2).collect();
+        assert_eq!(302, first[0].len());
+        assert_eq!(Some(&first[1]), arr.nth(1).as_ref());
+    }
+
+    #[test]
+    fn test_iter_exhausted() {
+        let arr = Arrangements::new(&vec![1, 2], &Tolerance::CLASSIC);
+        let mut iter = arr.iter();
+        assert_eq!(Some(vec![0, 1, 2, 5]), iter.next());
+        assert_eq!(Some(vec![0, 2, 5]), iter.next());
+        for _ in 0..3 {
+            assert_eq!(None, iter.next());
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let input = parse_input(INPUT);
+        let mut rng = Rng::new(42);
+        let mut seen: HashMap<Vec<Jolt>, usize> = HashMap::new();
+        for _ in 0..8000 {
+            let picked = sample(&input, &Tolerance::CLASSIC, &mut rng).unwrap();
+            *seen.entry(picked).or_insert(0) += 1;
+        }
+        assert_eq!(8, seen.len());
+        assert!(seen.values().all(|count| (850..1150).contains(count)));
+        let arr = Arrangements::new(&input, &Tolerance::CLASSIC);
+        assert!(arr.iter().all(|a| seen.contains_key(&a)));
+    }
+}
@@ -0,0 +1,101 @@
+//! Unsigned integers of any size, with just what counting arrangements needs.
+
+use std::{
+    fmt::{self, Display},
+    ops::Add,
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BigUint {
+    /// base 2^32 digits, least significant first, without leading zeros
+    digits: Vec<u32>,
+}
+
+impl From<u64> for BigUint {
+    fn from(n: u64) -> Self {
+        let mut big = BigUint {
+            digits: vec![n as u32, (n >> 32) as u32],
+        };
+        big.trim();
+        big
+    }
+}
+
+impl BigUint {
+    fn trim(&mut self) {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+
+    /// Divides in place, returns the remainder.
+    fn div_rem(&mut self, divisor: u32) -> u32 {
+        let mut rem: u64 = 0;
+        for digit in self.digits.iter_mut().rev() {
+            let cur = (rem << 32) | *digit as u64;
+            *digit = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        self.trim();
+        rem as u32
+    }
+}
+
+impl Add<&BigUint> for &BigUint {
+    type Output = BigUint;
+    fn add(self, other: &BigUint) -> BigUint {
+        let len = self.digits.len().max(other.digits.len());
+        let mut digits: Vec<u32> = Vec::with_capacity(len + 1);
+        let mut carry: u64 = 0;
+        for idx in 0..len {
+            let sum = carry
+                + *self.digits.get(idx).unwrap_or(&0) as u64
+                + *other.digits.get(idx).unwrap_or(&0) as u64;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        if carry > 0 {
+            digits.push(carry as u32);
+        }
+        BigUint { digits }
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // groups of 9 decimal digits, least significant first
+        let mut groups: Vec<u32> = Vec::new();
+        let mut rest = self.clone();
+        while !rest.digits.is_empty() {
+            groups.push(rest.div_rem(1_000_000_000));
+        }
+        match groups.split_last() {
+            None => f.write_str("0"),
+            Some((first, others)) => {
+                write!(f, "{}", first)?;
+                others.iter().rev().try_for_each(|g| write!(f, "{:09}", g))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_display() {
+        assert_eq!("0", BigUint::default().to_string());
+        assert_eq!(BigUint::default(), BigUint::from(0));
+        let max = BigUint::from(u64::MAX);
+        assert_eq!("18446744073709551615", max.to_string());
+        let sum = &max + &BigUint::from(1);
+        assert_eq!("18446744073709551616", sum.to_string());
+        assert_eq!("36893488147419103231", (&sum + &max).to_string());
+        let mut pow = BigUint::from(1);
+        for _ in 0..100 {
+            pow = &pow + &pow;
+        }
+        assert_eq!("1267650600228229401496703205376", pow.to_string());
+    }
+}
@@ -1,4 +1,8 @@
-use std::{env, fs};
+mod arrangements;
+mod big;
+
+use big::BigUint;
+use std::{collections::BTreeMap, env, error::Error, fs, time};
 
 fn get_input_path() -> String {
     let args: Vec<String> = env::args().collect();
@@ -8,66 +12,251 @@ fn get_input_path() -> String {
 type Jolt = u32;
 type Input = Vec<Jolt>;
 type Output1 = usize;
-type Output2 = usize;
+type Output2 = u64;
+
+/// Joltages accepted by a device family.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Tolerance {
+    /// smallest and largest difference between chained adapters
+    min_step: Jolt,
+    max_step: Jolt,
+    /// rating of the charging outlet
+    outlet: Jolt,
+    /// built-in adapter of the device, above the highest adapter
+    device_offset: Jolt,
+}
+
+impl Tolerance {
+    const CLASSIC: Tolerance = Tolerance {
+        min_step: 1,
+        max_step: 3,
+        outlet: 0,
+        device_offset: 3,
+    };
+
+    fn accepts(&self, from: Jolt, to: Jolt) -> bool {
+        to >= from + self.min_step && to <= from + self.max_step
+    }
+}
 
 fn parse_input(input: &str) -> Input {
     input.lines().map(|l| l.parse::<Jolt>().unwrap()).collect()
 }
 
-fn solve_part_1(input: &Input) -> Output1 {
-    let mut sorted = vec![0];
-    sorted.extend(input);
-    sorted.sort_by(|a, b| a.cmp(b));
-    let sumed =
-        sorted
-            .iter()
-            .zip(sorted.iter().skip(1))
-            .fold((0, 0), |(ones, threes), (prev, next)| {
-                let diff = next - prev;
-                if diff == 1 {
-                    (ones + 1, threes)
-                } else if diff == 3 {
-                    (ones, threes + 1)
-                } else {
-                    (ones, threes)
-                }
-            });
-    sumed.0 * (sumed.1 + 1)
-}
-
-fn solve_part_2(input: &Input) -> Output2 {
-    let mut sorted = vec![0];
-    sorted.extend(input);
-    sorted.sort();
-    let mut pathes: Vec<usize> = Vec::with_capacity(sorted.len());
-    pathes.push(1);
-    for _ in 1..sorted.len() {
-        pathes.push(0);
+/// Outlet, sorted adapters then device.
+fn chain(input: &Input, tolerance: &Tolerance) -> Vec<Jolt> {
+    let mut sorted = input.clone();
+    sorted.sort_unstable();
+    let device = sorted.last().unwrap_or(&tolerance.outlet) + tolerance.device_offset;
+    sorted.insert(0, tolerance.outlet);
+    sorted.push(device);
+    sorted
+}
+
+/// Count of each difference between consecutive joltages of the chain.
+fn histogram(chain: &[Jolt]) -> BTreeMap<Jolt, usize> {
+    let mut diffs: BTreeMap<Jolt, usize> = BTreeMap::new();
+    for pair in chain.windows(2) {
+        *diffs.entry(pair[1] - pair[0]).or_insert(0) += 1;
     }
+    diffs
+}
+
+fn solve_part_1(input: &Input, tolerance: &Tolerance) -> Result<Output1, Box<dyn Error>> {
+    let chain = chain(input, tolerance);
+    if let Some(pair) = chain.windows(2).find(|p| !tolerance.accepts(p[0], p[1])) {
+        return Err(format!("Can't chain {} jolts to {} jolts", pair[0], pair[1]).into());
+    }
+    let diffs = histogram(&chain);
+    let count = |diff: Jolt| diffs.get(&diff).copied().unwrap_or(0);
+    Ok(count(1) * count(3))
+}
+
+/// How arrangements are counted, chosen with `--count=mod:<p>` or
+/// `--count=big`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CountMode {
+    /// 64 bits, failing on overflow
+    Checked,
+    Modulo(u64),
+    Big,
+}
+
+/// Ways to go from the outlet to each joltage of `sorted`, counted with `add`
+/// which returns `None` on overflow.
+fn path_counts<T: Clone>(
+    sorted: &[Jolt],
+    tolerance: &Tolerance,
+    zero: T,
+    one: T,
+    add: impl Fn(&T, &T) -> Option<T>,
+) -> Option<Vec<T>> {
+    let mut pathes: Vec<T> = vec![zero; sorted.len()];
+    pathes[0] = one;
     // build pathes vector, it contains count of each arriving pathes
     // the first path gets a weight of 1 and it applies it to its children
     // each parent increases its children weight by its own (the number of path arriving to it)
     for (idx, jolt) in sorted.iter().enumerate() {
-        for (shift, _) in sorted[idx + 1..]
+        let weight = pathes[idx].clone();
+        for (shift, item) in sorted[idx + 1..]
             .iter()
-            .take_while(|&item| *item <= *jolt + 3)
+            .take_while(|&item| *item <= *jolt + tolerance.max_step)
             .enumerate()
         {
-            pathes[idx + shift + 1] += pathes[idx];
+            if tolerance.accepts(*jolt, *item) {
+                pathes[idx + shift + 1] = add(&pathes[idx + shift + 1], &weight)?;
+            }
+        }
+    }
+    Some(pathes)
+}
+
+/// Ways to go from the outlet to the device.
+fn count_arrangements<T: Clone>(
+    input: &Input,
+    tolerance: &Tolerance,
+    zero: T,
+    one: T,
+    add: impl Fn(&T, &T) -> Option<T>,
+) -> Option<T> {
+    let sorted = chain(input, tolerance);
+    // the device will contain the cumulated weight (possible paths) leading to it
+    path_counts(&sorted, tolerance, zero, one, add)?.pop()
+}
+
+fn solve_part_2(input: &Input, tolerance: &Tolerance) -> Result<Output2, Box<dyn Error>> {
+    count_arrangements(input, tolerance, 0, 1, |a: &u64, b| a.checked_add(*b))
+        .ok_or_else(|| "Arrangement count overflows, use --count=mod:<p> or --count=big".into())
+}
+
+fn count_modulo(input: &Input, tolerance: &Tolerance, modulus: u64) -> u64 {
+    count_arrangements(input, tolerance, 0, 1 % modulus, |a: &u64, b| {
+        Some(((*a as u128 + *b as u128) % modulus as u128) as u64)
+    })
+    .unwrap()
+}
+
+fn count_big(input: &Input, tolerance: &Tolerance) -> BigUint {
+    count_arrangements(
+        input,
+        tolerance,
+        BigUint::from(0),
+        BigUint::from(1),
+        |a, b| Some(a + b),
+    )
+    .unwrap()
+}
+
+fn get_count_mode() -> CountMode {
+    match env::args().find_map(|a| a.strip_prefix("--count=").map(String::from)) {
+        None => CountMode::Checked,
+        Some(mode) if mode == "big" => CountMode::Big,
+        Some(mode) => match mode.strip_prefix("mod:").map(|p| p.parse()) {
+            Some(Ok(modulus)) if modulus > 0 => CountMode::Modulo(modulus),
+            _ => panic!("Expected --count=mod:<p> or --count=big"),
+        },
+    }
+}
+
+/// Tolerance overridden by `--steps=<min>,<max>`, `--outlet=<jolts>` and
+/// `--device-offset=<jolts>` options.
+fn get_tolerance() -> Tolerance {
+    let mut tolerance = Tolerance::CLASSIC;
+    for arg in env::args() {
+        if let Some(steps) = arg.strip_prefix("--steps=") {
+            let (min, max) = steps.split_once(',').expect("Expected --steps=<min>,<max>");
+            tolerance.min_step = min.parse().unwrap();
+            tolerance.max_step = max.parse().unwrap();
+        } else if let Some(outlet) = arg.strip_prefix("--outlet=") {
+            tolerance.outlet = outlet.parse().unwrap();
+        } else if let Some(offset) = arg.strip_prefix("--device-offset=") {
+            tolerance.device_offset = offset.parse().unwrap();
+        }
+    }
+    tolerance
+}
+
+/// Arrangements to print instead of solving the puzzle.
+#[derive(Debug, Clone, Copy)]
+enum ArrangementAction {
+    /// the first ones in lexicographic order
+    List(usize),
+    Nth(u64),
+    /// random ones, with a seed
+    Sample(usize, u64),
+}
+
+fn get_arrangement_action() -> Option<ArrangementAction> {
+    let value = |name: &str| {
+        env::args().find_map(|a| {
+            a.strip_prefix(name)
+                .map(|v| v.parse::<u64>().expect("Expected a number"))
+        })
+    };
+    let seed = value("--seed=").unwrap_or_else(|| {
+        time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64
+    });
+    if let Some(count) = value("--list=") {
+        Some(ArrangementAction::List(count as usize))
+    } else if let Some(k) = value("--nth=") {
+        Some(ArrangementAction::Nth(k))
+    } else {
+        value("--sample=").map(|count| ArrangementAction::Sample(count as usize, seed))
+    }
+}
+
+fn print_arrangements(input: &Input, tolerance: &Tolerance, action: ArrangementAction) {
+    let print = |jolts: &[Jolt]| {
+        let jolts: Vec<String> = jolts.iter().map(|j| j.to_string()).collect();
+        println!("{}", jolts.join(" "));
+    };
+    let all = arrangements::Arrangements::new(input, tolerance);
+    match action {
+        ArrangementAction::List(count) => all.iter().take(count).for_each(|a| print(&a)),
+        ArrangementAction::Nth(k) => match all.nth(k) {
+            Some(a) => print(&a),
+            None => println!(
+                "There are only {} arrangements",
+                count_big(input, tolerance)
+            ),
+        },
+        ArrangementAction::Sample(count, seed) => {
+            let mut rng = arrangements::Rng::new(seed);
+            for _ in 0..count {
+                print(&arrangements::sample(input, tolerance, &mut rng).unwrap());
+            }
         }
     }
-    // the last item will contain the cumulated weight (possible paths) leading to it
-    *pathes.last().unwrap()
 }
 
 fn main() {
     let input_path = get_input_path();
     let raw_input = fs::read_to_string(input_path).unwrap();
     let input = parse_input(&raw_input);
-    let part_1_result = solve_part_1(&input);
-    println!("Part 1: {:?}", part_1_result);
-    let part_2_result = solve_part_2(&input);
-    println!("Part 2: {:?}", part_2_result);
+    let tolerance = get_tolerance();
+    if env::args().any(|a| a == "--histogram") {
+        for (diff, count) in histogram(&chain(&input, &tolerance)) {
+            println!("{} jolts: {}", diff, count);
+        }
+    }
+    if let Some(action) = get_arrangement_action() {
+        print_arrangements(&input, &tolerance, action);
+        return;
+    }
+    let part_1_result = solve_part_1(&input, &tolerance);
+    println!("Part 1: {:?}", part_1_result.unwrap());
+    match get_count_mode() {
+        CountMode::Checked => println!("Part 2: {:?}", solve_part_2(&input, &tolerance).unwrap()),
+        CountMode::Modulo(modulus) => println!(
+            "Part 2: {} (mod {})",
+            count_modulo(&input, &tolerance, modulus),
+            modulus
+        ),
+        CountMode::Big => println!("Part 2: {}", count_big(&input, &tolerance)),
+    }
 }
 
 #[cfg(test)]
@@ -119,28 +308,79 @@ mod tests {
     #[test]
     fn test_ex1_part1() {
         let input = parse_input(EX1_INPUT);
-        let res = solve_part_1(&input);
+        let res = solve_part_1(&input, &Tolerance::CLASSIC).unwrap();
         assert_eq!(7 * 5, res);
     }
 
     #[test]
     fn test_ex2_part1() {
         let input = parse_input(EX2_INPUT);
-        let res = solve_part_1(&input);
+        let res = solve_part_1(&input, &Tolerance::CLASSIC).unwrap();
         assert_eq!(22 * 10, res);
     }
 
     #[test]
     fn test_ex1_part2() {
         let input = parse_input(EX1_INPUT);
-        let res = solve_part_2(&input);
+        let res = solve_part_2(&input, &Tolerance::CLASSIC).unwrap();
         assert_eq!(8, res);
     }
 
     #[test]
     fn test_ex2_part2() {
         let input = parse_input(EX2_INPUT);
-        let res = solve_part_2(&input);
+        let res = solve_part_2(&input, &Tolerance::CLASSIC).unwrap();
         assert_eq!(19208, res);
     }
+
+    #[test]
+    fn test_histogram() {
+        let input = parse_input(EX2_INPUT);
+        let diffs = histogram(&chain(&input, &Tolerance::CLASSIC));
+        assert_eq!(
+            vec![(1, 22), (3, 10)],
+            diffs.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_tolerance() {
+        let input = parse_input(EX1_INPUT);
+        let wide = Tolerance {
+            min_step: 2,
+            max_step: 4,
+            outlet: 0,
+            device_offset: 2,
+        };
+        assert_eq!(
+            vec![0, 1, 4, 5, 6, 7, 10, 11, 12, 15, 16, 19, 21],
+            chain(&input, &wide)
+        );
+        assert_eq!(5, solve_part_2(&input, &wide).unwrap());
+        assert_eq!(
+            "Can't chain 0 jolts to 1 jolts",
+            solve_part_1(&input, &wide).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_large_counts() {
+        // every joltage from 1 to 300, arrangements follow the tribonacci sequence
+        let input: Input = (1..=300).collect();
+        let tolerance = Tolerance::CLASSIC;
+        assert!(solve_part_2(&input, &tolerance).is_err());
+        let big = count_big(&input, &tolerance).to_string();
+        assert_eq!(
+            "15350287614359738671843506567023635268924281173051801861566524609184461020990367",
+            big
+        );
+        let modulus = 1_000_000_007;
+        let expected = big
+            .bytes()
+            .fold(0, |rem, d| (rem * 10 + (d - b'0') as u64) % modulus);
+        assert_eq!(expected, count_modulo(&input, &tolerance, modulus));
+        let input = parse_input(EX2_INPUT);
+        assert_eq!("19208", count_big(&input, &tolerance).to_string());
+        assert_eq!(19208 % 1000, count_modulo(&input, &tolerance, 1000));
+    }
 }
@@ -3,8 +3,6 @@ use std::{
     env,
     fmt::{self, Display, Write},
     fs,
-    iter::Iterator,
-    ops::RangeInclusive,
 };
 
 fn get_input_path() -> String {
@@ -70,30 +68,18 @@ impl Grid {
         self.cells.get_mut(y * self.width + x)
     }
 
-    pub fn neighbours<'s>(&'s self, x: usize, y: usize) -> Neighbours<'s> {
-        let x_min = if x == 0 { 0 } else { -1 };
-        let x_max = if x == self.width - 1 { 0 } else { 1 };
-        let y_min = if y == 0 { 0 } else { -1 };
-        let y_max = if y == self.height - 1 { 0 } else { 1 };
-        Neighbours {
-            x: x as u32,
-            y: y as u32,
-            dx: (x_min..=x_max),
-            dy: (y_min..=y_max),
-            off_x: x_min,
-            off_y: y_min,
-            grid: self,
-        }
-    }
-
-    pub fn toward<'s>(&'s self, x: usize, y: usize, dir: (i8, i8)) -> Toward<'s> {
-        Toward {
-            grid: self,
-            x,
-            y,
-            dir,
-            off: 1,
-        }
+    /// Index of the first seat in direction `dir` from `idx`, looking at most
+    /// `reach` cells away.
+    pub fn seen(&self, idx: usize, dir: (i8, i8), reach: usize) -> Option<usize> {
+        let (x, y) = ((idx % self.width) as i64, (idx / self.width) as i64);
+        (1..=reach as i64)
+            .map(|off| (x + off * dir.0 as i64, y + off * dir.1 as i64))
+            .take_while(|(x, y)| {
+                *x >= 0 && *x < self.width as i64 && *y >= 0 && *y < self.height as i64
+            })
+            .map(|(x, y)| (x as usize, y as usize))
+            .find(|(x, y)| self.cell(*x, *y) != Some(&Cell::Floor))
+            .map(|(x, y)| y * self.width + x)
     }
 }
 impl Display for Grid {
@@ -112,63 +98,6 @@ impl Display for Grid {
         Ok(())
     }
 }
-struct Neighbours<'n> {
-    x: u32,
-    y: u32,
-    dx: RangeInclusive<i8>,
-    dy: RangeInclusive<i8>,
-    off_x: i8,
-    off_y: i8,
-    grid: &'n Grid,
-}
-impl<'n> Neighbours<'n> {
-    pub fn shift(&mut self) {
-        self.off_x += 1;
-        if self.off_x > *self.dx.end() {
-            self.off_y += 1;
-            self.off_x = *self.dx.start();
-        }
-    }
-}
-impl<'n> Iterator for Neighbours<'n> {
-    type Item = &'n Cell;
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.off_y > *self.dy.end() {
-            return None;
-        }
-        if self.off_x == 0 && self.off_y == 0 {
-            self.shift();
-        }
-        let cell = self.grid.cell(
-            (self.x as i32 + self.off_x as i32) as usize,
-            (self.y as i32 + self.off_y as i32) as usize,
-        );
-        if self.off_x <= *self.dx.end() {
-            self.shift();
-        }
-        cell
-    }
-}
-struct Toward<'n> {
-    grid: &'n Grid,
-    x: usize,
-    y: usize,
-    dir: (i8, i8),
-    off: usize,
-}
-impl<'n> Iterator for Toward<'n> {
-    type Item = Cell;
-    fn next(&mut self) -> Option<Self::Item> {
-        let x = self.x as i64 + self.off as i64 * self.dir.0 as i64;
-        let y = self.y as i64 + self.off as i64 * self.dir.1 as i64;
-        if x < 0 || x >= self.grid.width as i64 || y < 0 || y >= self.grid.height as i64 {
-            return None;
-        }
-        let cell = self.grid.cell(x as usize, y as usize);
-        self.off += 1;
-        cell.copied()
-    }
-}
 type Input = Grid;
 type Output1 = usize;
 type Output2 = usize;
@@ -182,124 +111,237 @@ fn parse_input(input: &str) -> Input {
     grid
 }
 
-fn simul_round(grid: &mut Grid, mirror: &mut Grid) -> bool {
-    let mut change = false;
-    for (idx, cell) in grid.cells.iter().enumerate() {
-        if let Cell::Floor = cell {
-            continue;
-        }
-        let neighbours = grid
-            .neighbours(idx % grid.width, idx / grid.width)
-            .filter(|&c| *c == Cell::Seat(Occupation::Occupied))
-            .count();
-        let cell = match cell {
-            Cell::Seat(Occupation::Empty) => {
-                if neighbours == 0 {
-                    change = true;
-                    Cell::Seat(Occupation::Occupied)
-                } else {
-                    *cell
-                }
-            }
-            Cell::Seat(Occupation::Occupied) => {
-                if neighbours >= 4 {
-                    change = true;
-                    Cell::Seat(Occupation::Empty)
-                } else {
-                    *cell
-                }
-            }
-            _ => *cell,
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Neighbourhood {
+    /// the eight surrounding cells
+    Adjacent,
+    /// the first seat seen in each of the eight directions
+    InSight,
+}
+
+const DIRECTIONS: [(i8, i8); 8] = [
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+];
+
+impl Neighbourhood {
+    /// Seats around each cell, computed once since floor never changes. It
+    /// needn't be symmetric, `Simulation` reverses it to update the counts.
+    fn graph(&self, grid: &Grid) -> Vec<Vec<usize>> {
+        let reach = match self {
+            Neighbourhood::Adjacent => 1,
+            Neighbourhood::InSight => grid.width.max(grid.height),
         };
-        *mirror.cell_idx_mut(idx).unwrap() = cell;
+        (0..grid.cells.len())
+            .map(|idx| match grid.cells[idx] {
+                Cell::Floor => Vec::new(),
+                Cell::Seat(_) => DIRECTIONS
+                    .iter()
+                    .filter_map(|dir| grid.seen(idx, *dir, reach))
+                    .collect(),
+            })
+            .collect()
     }
-    grid.cells.swap_with_slice(mirror.cells.as_mut_slice());
-    change
 }
 
-fn solve_part_1(input: &Input) -> Output1 {
-    let mut grid = input.clone();
-    let mut mirror = input.clone();
-    let mut change = true;
-    while change {
-        change = simul_round(&mut grid, &mut mirror);
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Thresholds {
+    /// an empty seat is taken with at most this many occupied neighbours
+    take: usize,
+    /// an occupied seat is left with at least this many occupied neighbours
+    leave: usize,
+}
+
+/// Next state of a seat given its count of occupied neighbours.
+type Transition = fn(Occupation, usize, &Thresholds) -> Occupation;
+
+fn seating(seat: Occupation, occupied: usize, thresholds: &Thresholds) -> Occupation {
+    match seat {
+        Occupation::Empty if occupied <= thresholds.take => Occupation::Occupied,
+        Occupation::Occupied if occupied >= thresholds.leave => Occupation::Empty,
+        _ => seat,
     }
-    grid.cells
-        .iter()
-        .filter(|&c| *c == Cell::Seat(Occupation::Occupied))
-        .count()
 }
 
-fn simul_round_2(grid: &mut Grid, mirror: &mut Grid) -> bool {
-    let mut change = false;
-    for (idx, cell) in grid.cells.iter().enumerate() {
-        if let Cell::Floor = cell {
-            continue;
+/// Seating policy run by a `Simulation`.
+#[derive(Clone, Copy)]
+struct Rule {
+    neighbourhood: Neighbourhood,
+    thresholds: Thresholds,
+    transition: Transition,
+}
+
+impl Rule {
+    const ADJACENT: Rule = Rule {
+        neighbourhood: Neighbourhood::Adjacent,
+        thresholds: Thresholds { take: 0, leave: 4 },
+        transition: seating,
+    };
+    const IN_SIGHT: Rule = Rule {
+        neighbourhood: Neighbourhood::InSight,
+        thresholds: Thresholds { take: 0, leave: 5 },
+        transition: seating,
+    };
+}
+
+/// Seating under a rule, re-evaluating only the seats whose neighbourhood
+/// changed during the previous round.
+struct Simulation<'r> {
+    grid: Grid,
+    rule: &'r Rule,
+    /// seats seeing each cell, the reverse of the neighbourhood graph
+    seen_by: Vec<Vec<usize>>,
+    /// occupied neighbours of each cell
+    occupied: Vec<usize>,
+    /// seats to evaluate next round
+    dirty: Vec<usize>,
+    /// cells already in `dirty`
+    queued: Vec<bool>,
+}
+
+impl<'r> Simulation<'r> {
+    fn new(input: &Input, rule: &'r Rule) -> Simulation<'r> {
+        let graph = rule.neighbourhood.graph(input);
+        let mut seen_by: Vec<Vec<usize>> = vec![Vec::new(); graph.len()];
+        for (idx, seen) in graph.iter().enumerate() {
+            for other in seen.iter() {
+                seen_by[*other].push(idx);
+            }
+        }
+        let occupied = graph
+            .iter()
+            .map(|seen| {
+                seen.iter()
+                    .filter(|idx| input.cells[**idx] == Cell::Seat(Occupation::Occupied))
+                    .count()
+            })
+            .collect();
+        let dirty = (0..input.cells.len())
+            .filter(|idx| input.cells[*idx] != Cell::Floor)
+            .collect();
+        Simulation {
+            grid: input.clone(),
+            rule,
+            seen_by,
+            occupied,
+            dirty,
+            queued: vec![false; input.cells.len()],
         }
-        const DIRECTIONS: [(i8, i8); 8] = [
-            (-1, 0),
-            (-1, -1),
-            (0, -1),
-            (1, -1),
-            (1, 0),
-            (1, 1),
-            (0, 1),
-            (-1, 1),
-        ];
-        let in_sight = DIRECTIONS
+    }
+
+    /// Runs one round, returns the seats which changed in increasing order.
+    fn round(&mut self) -> Vec<(usize, Occupation)> {
+        let rule = self.rule;
+        let mut changes: Vec<(usize, Occupation)> = self
+            .dirty
             .iter()
-            .map(|dir| {
-                grid.toward(idx % grid.width, idx / grid.width, *dir)
-                    .find(|&c| c != Cell::Floor)
+            .filter_map(|idx| match self.grid.cells[*idx] {
+                Cell::Seat(seat) => {
+                    let next = (rule.transition)(seat, self.occupied[*idx], &rule.thresholds);
+                    Some((*idx, next)).filter(|_| next != seat)
+                }
+                Cell::Floor => None,
             })
-            .filter(|&c| c == Some(Cell::Seat(Occupation::Occupied)))
-            .count();
-        let cell = match cell {
-            Cell::Seat(Occupation::Empty) => {
-                if in_sight == 0 {
-                    change = true;
-                    Cell::Seat(Occupation::Occupied)
-                } else {
-                    *cell
+            .collect();
+        for idx in self.dirty.iter() {
+            self.queued[*idx] = false;
+        }
+        self.dirty.clear();
+        for (idx, seat) in changes.iter() {
+            *self.grid.cell_idx_mut(*idx).unwrap() = Cell::Seat(*seat);
+            for other in self.seen_by[*idx].iter() {
+                match seat {
+                    Occupation::Occupied => self.occupied[*other] += 1,
+                    Occupation::Empty => self.occupied[*other] -= 1,
                 }
             }
-            Cell::Seat(Occupation::Occupied) => {
-                if in_sight >= 5 {
-                    change = true;
-                    Cell::Seat(Occupation::Empty)
-                } else {
-                    *cell
+            for seen in self.seen_by[*idx].iter().chain(std::iter::once(idx)) {
+                if !self.queued[*seen] {
+                    self.queued[*seen] = true;
+                    self.dirty.push(*seen);
                 }
             }
-            _ => *cell,
-        };
-        *mirror.cell_idx_mut(idx).unwrap() = cell;
+        }
+        changes.sort_unstable_by_key(|(idx, _)| *idx);
+        changes
     }
-    grid.cells.swap_with_slice(mirror.cells.as_mut_slice());
-    change
 }
 
-fn solve_part_2(input: &Input) -> Output2 {
-    let mut grid = input.clone();
-    let mut mirror = input.clone();
-    let mut change = true;
-    while change {
-        change = simul_round_2(&mut grid, &mut mirror);
+/// Runs `rule` until no seat changes, `None` if it keeps changing.
+///
+/// Seating under thresholds either settles or ends up alternating between
+/// two grids, which shows as a round flipping back the seats the previous
+/// round flipped.
+fn simulate(input: &Input, rule: &Rule) -> Option<Grid> {
+    let mut simulation = Simulation::new(input, rule);
+    let mut previous: Vec<(usize, Occupation)> = Vec::new();
+    loop {
+        let changes = simulation.round();
+        if changes.is_empty() {
+            return Some(simulation.grid);
+        }
+        let flipped_back = changes.len() == previous.len()
+            && changes
+                .iter()
+                .zip(previous.iter())
+                .all(|((idx, seat), (prev_idx, prev_seat))| idx == prev_idx && seat != prev_seat);
+        if flipped_back {
+            return None;
+        }
+        previous = changes;
     }
+}
+
+fn count_occupied(grid: &Grid) -> usize {
     grid.cells
         .iter()
         .filter(|&c| *c == Cell::Seat(Occupation::Occupied))
         .count()
 }
 
+fn solve_part_1(input: &Input, rule: &Rule) -> Option<Output1> {
+    Some(count_occupied(&simulate(input, rule)?))
+}
+
+fn solve_part_2(input: &Input, rule: &Rule) -> Option<Output2> {
+    solve_part_1(input, rule)
+}
+
+/// `rule` with thresholds overridden by a `<option>=<take>,<leave>` argument.
+fn get_rule(rule: Rule, option: &str) -> Rule {
+    let thresholds = env::args().find_map(|a| {
+        let (take, leave) = a.strip_prefix(option)?.split_once(',')?;
+        Some(Thresholds {
+            take: take.parse().unwrap(),
+            leave: leave.parse().unwrap(),
+        })
+    });
+    Rule {
+        thresholds: thresholds.unwrap_or(rule.thresholds),
+        ..rule
+    }
+}
+
 fn main() {
     let input_path = get_input_path();
     let raw_input = fs::read_to_string(input_path).unwrap();
     let input = parse_input(&raw_input);
-    let part_1_result = solve_part_1(&input);
-    println!("Part 1: {:?}", part_1_result);
-    let part_2_result = solve_part_2(&input);
-    println!("Part 2: {:?}", part_2_result);
+    let part_1_result = solve_part_1(&input, &get_rule(Rule::ADJACENT, "--adjacent="));
+    println!(
+        "Part 1: {:?}",
+        part_1_result.expect("Seating never settles")
+    );
+    let part_2_result = solve_part_2(&input, &get_rule(Rule::IN_SIGHT, "--in-sight="));
+    println!(
+        "Part 2: {:?}",
+        part_2_result.expect("Seating never settles")
+    );
 }
 
 #[cfg(test)]
@@ -322,34 +364,79 @@ L.LLLLL.LL";
     }
     #[test]
     fn test_simul() {
-        let mut grid = parse_input(EXAMPLE);
-        let mut mirror = grid.clone();
-        println!("0. {}\n", grid);
-        simul_round(&mut grid, &mut mirror);
-        println!("1. {}\n", grid);
-        simul_round(&mut grid, &mut mirror);
-        println!("2. {}\n", grid);
+        let grid = parse_input(EXAMPLE);
+        let mut simulation = Simulation::new(&grid, &Rule::ADJACENT);
+        simulation.round();
+        simulation.round();
+        assert_eq!(
+            "w10 h10
+#.LL.L#.##
+#LLLLLL.L#
+L.L.L..L..
+#LLL.LL.L#
+#.LL.LL.LL
+#.LLLL#.##
+..L.L.....
+#LLLLLLLL#
+#.LLLLLL.L
+#.#LLLL.##",
+            simulation.grid.to_string()
+        );
     }
     #[test]
     fn test_part1() {
         let input = parse_input(EXAMPLE);
-        let res = solve_part_1(&input);
-        assert_eq!(37, res);
+        let res = solve_part_1(&input, &Rule::ADJACENT);
+        assert_eq!(Some(37), res);
     }
     #[test]
     fn test_simul2() {
-        let mut grid = parse_input(EXAMPLE);
-        let mut mirror = grid.clone();
-        println!("0. {}\n", grid);
-        simul_round_2(&mut grid, &mut mirror);
-        println!("1. {}\n", grid);
-        simul_round_2(&mut grid, &mut mirror);
-        println!("2. {}\n", grid);
+        let grid = parse_input(EXAMPLE);
+        let mut simulation = Simulation::new(&grid, &Rule::IN_SIGHT);
+        simulation.round();
+        simulation.round();
+        assert_eq!(
+            "w10 h10
+#.LL.LL.L#
+#LLLLLL.LL
+L.L.L..L..
+LLLL.LL.LL
+L.LL.LL.LL
+L.LLLLL.LL
+..L.L.....
+LLLLLLLLL#
+#.LLLLLL.L
+#.LLLLL.L#",
+            simulation.grid.to_string()
+        );
     }
     #[test]
     fn test_part2() {
         let input = parse_input(EXAMPLE);
-        let res = solve_part_2(&input);
-        assert_eq!(26, res);
+        let res = solve_part_2(&input, &Rule::IN_SIGHT);
+        assert_eq!(Some(26), res);
+    }
+    #[test]
+    fn test_thresholds() {
+        let input = parse_input(EXAMPLE);
+        // nobody leaves: every seat is taken in the first round
+        let never_leave = Rule {
+            thresholds: Thresholds { take: 0, leave: 9 },
+            ..Rule::ADJACENT
+        };
+        assert_eq!(Some(71), solve_part_1(&input, &never_leave));
+        // a policy emptying every seat
+        let vacate = Rule {
+            transition: |_, _, _| Occupation::Empty,
+            ..Rule::IN_SIGHT
+        };
+        let full = simulate(&input, &never_leave).unwrap();
+        assert_eq!(0, count_occupied(&simulate(&full, &vacate).unwrap()));
+        // occupied seats with a neighbour in sight are left, then taken back
+        let shy = Rule {
+            thresholds: Thresholds { take: 0, leave: 1 },
+            ..Rule::IN_SIGHT
+        };
+        assert_eq!(None, solve_part_2(&input, &shy));
     }
 }